@@ -0,0 +1,572 @@
+//! Hand analysis, for planning shows and choosing where to insert scouted cards.
+
+use crate::{Card, SetMap};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// An ordered sequence of shows which empties a hand.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// Inclusive (start, stop) ranges, as in `Action::Show`. Each range indexes into the hand
+    /// as it is after the previous shows.
+    pub shows: Vec<(usize, usize)>,
+    /// The set formed by each show
+    pub sets: Vec<Vec<i32>>,
+}
+
+impl Plan {
+    /// Number of show turns required by the plan.
+    pub fn turns(&self) -> usize {
+        self.shows.len()
+    }
+}
+
+/// A position and orientation to insert a scouted card, with measures of the resulting hand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Insertion {
+    pub index: usize,
+    pub flip: bool,
+    /// Minimum number of shows needed to empty the new hand
+    pub turns_to_empty: usize,
+    /// Length of the longest set containing the inserted card
+    pub largest_set: usize,
+    /// Number of sets of two or more cards containing the inserted card, which weren't possible
+    /// before
+    pub sets_created: usize,
+    /// Number of sets in the old hand which the inserted card splits, such as inserting between
+    /// the 2 and 3 of a run 1, 2, 3 splitting both [1, 2] and [1, 2, 3]
+    pub sets_broken: usize,
+}
+
+impl Insertion {
+    /// Change in the number of sets in the hand, which is negative if more sets were broken
+    /// than created.
+    pub fn net_sets(&self) -> isize {
+        self.sets_created as isize - self.sets_broken as isize
+    }
+}
+
+/// Kinds of valid set. A single card is neither a straight nor a flush.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Hash)]
+pub enum SetKind {
+    Single,
+    Straight,
+    Flush,
+}
+
+/// A valid set within a hand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandSet {
+    /// Inclusive bounds of the set, as in `Action::Show`
+    pub start: usize,
+    pub stop: usize,
+    pub kind: SetKind,
+    pub len: usize,
+    /// Value of the set in the `SetMap`, higher sets beat lower sets
+    pub rank: i32,
+}
+
+/// Every valid set in a hand, ordered from strongest to weakest.
+#[derive(Clone, Debug, Default)]
+pub struct HandSets(Vec<HandSet>);
+
+impl HandSets {
+    pub fn strongest(&self) -> Option<&HandSet> {
+        self.0.first()
+    }
+
+    /// Sets which beat a set with the given `rank`. An empty active set has rank 0.
+    pub fn beating(&self, rank: i32) -> &[HandSet] {
+        &self.0[..self.0.partition_point(|set| set.rank > rank)]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HandSet> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Find every contiguous valid set in `hand`, ranked by `set_map`.
+pub fn find_sets(hand: &[i32], set_map: &SetMap) -> HandSets {
+    let mut sets = Vec::new();
+    for start in 0..hand.len() {
+        for stop in start..hand.len() {
+            // Extending an invalid set never makes it valid
+            let rank = match set_map.get(&hand[start..stop + 1]) {
+                Some(rank) => *rank,
+                None => break,
+            };
+            sets.push(HandSet {
+                start,
+                stop,
+                kind: set_kind(&hand[start..stop + 1]),
+                len: stop + 1 - start,
+                rank,
+            });
+        }
+    }
+    sets.sort_by_key(|set| Reverse(set.rank));
+    HandSets(sets)
+}
+
+/// Kind of a valid, non-empty set.
+fn set_kind(set: &[i32]) -> SetKind {
+    match (set.len(), set[0] == set[set.len() - 1]) {
+        (1, _) => SetKind::Single,
+        (_, true) => SetKind::Flush,
+        (_, false) => SetKind::Straight,
+    }
+}
+
+/// Reason for the result of `compare_sets`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Reason {
+    /// This set isn't valid, so loses to any valid set (including the empty set)
+    Invalid(Vec<i32>),
+    /// Any valid set beats the empty set
+    Empty,
+    /// The longer set wins
+    Longer,
+    /// A flush beats a straight of the same length
+    FlushBeatsStraight,
+    /// A straight beats a flush of the same length, under `SetOrdering::StraightsHigher`
+    StraightBeatsFlush,
+    /// Sets of the same length and kind are compared by value
+    HigherValue,
+    /// The sets are equally strong
+    Equal,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Invalid(set) => write!(f, "{:?} is not a valid set", set),
+            Self::Empty => write!(f, "any set beats an empty set"),
+            Self::Longer => write!(f, "longer set wins"),
+            Self::FlushBeatsStraight => write!(f, "flush beats straight of same length"),
+            Self::StraightBeatsFlush => write!(f, "straight beats flush of same length"),
+            Self::HigherValue => write!(f, "higher value wins"),
+            Self::Equal => write!(f, "sets are equally strong"),
+        }
+    }
+}
+
+/// Compare the strength of set `a` to set `b`. Invalid sets are weaker than any valid set,
+/// and the empty set is weaker than any non-empty valid set.
+pub fn compare_sets(a: &[i32], b: &[i32], set_map: &SetMap) -> Ordering {
+    compare_sets_with_reason(a, b, set_map).0
+}
+
+/// The reason for the result of `compare_sets`.
+pub fn explain_comparison(a: &[i32], b: &[i32], set_map: &SetMap) -> Reason {
+    compare_sets_with_reason(a, b, set_map).1
+}
+
+fn compare_sets_with_reason(a: &[i32], b: &[i32], set_map: &SetMap) -> (Ordering, Reason) {
+    let rank = |set: &[i32]| match set.is_empty() {
+        true => Some(0),
+        false => set_map.get(set).copied(),
+    };
+    match (rank(a), rank(b)) {
+        (None, None) => (Ordering::Equal, Reason::Invalid(a.to_vec())),
+        (None, Some(_)) => (Ordering::Less, Reason::Invalid(a.to_vec())),
+        (Some(_), None) => (Ordering::Greater, Reason::Invalid(b.to_vec())),
+        (Some(rank_a), Some(rank_b)) => {
+            let ordering = rank_a.cmp(&rank_b);
+            let reason = if ordering == Ordering::Equal {
+                Reason::Equal
+            } else if a.is_empty() || b.is_empty() {
+                Reason::Empty
+            } else if a.len() != b.len() {
+                Reason::Longer
+            } else if set_kind(a) != set_kind(b) {
+                // The set map ranks the kinds, so the winner's kind gives the reason
+                let winner = if ordering == Ordering::Greater { a } else { b };
+                match set_kind(winner) {
+                    SetKind::Flush => Reason::FlushBeatsStraight,
+                    _ => Reason::StraightBeatsFlush,
+                }
+            } else {
+                Reason::HigherValue
+            };
+            (ordering, reason)
+        }
+    }
+}
+
+/// Analyses hands against a `SetMap`, caching the number of turns required to empty each hand.
+/// A hand and its reverse always need the same number of turns, so share a cache entry.
+pub struct HandAnalyser {
+    set_map: SetMap,
+    cache: HashMap<Vec<i32>, usize>,
+}
+
+impl HandAnalyser {
+    pub fn new(set_map: SetMap) -> HandAnalyser {
+        HandAnalyser {
+            set_map,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn set_map(&self) -> &SetMap {
+        &self.set_map
+    }
+
+    /// Returns minimum number of show actions required to empty hand.
+    /// This iterates through all possible sets, checks validity against the `SetMap`,
+    /// then evaluates remaining hand recursively.
+    pub fn turns_to_empty(&mut self, hand: &[i32]) -> usize {
+        if hand.is_empty() {
+            return 0;
+        }
+        if self.set_map.contains_key(hand) {
+            return 1;
+        }
+
+        let key = canonical(hand);
+        if let Some(turns) = self.cache.get(&key) {
+            return *turns;
+        }
+
+        let mut turns = usize::MAX;
+        for (start, stop) in self.valid_ranges(hand) {
+            turns = turns.min(self.turns_to_empty(&without(hand, start, stop)) + 1);
+            if turns == 2 {
+                break; // Can't do better than a set leaving a set
+            }
+        }
+
+        // Cache!
+        self.cache.insert(key, turns);
+        turns
+    }
+
+    /// Returns an optimal `Plan` to empty `hand`.
+    pub fn plan(&mut self, hand: &[i32]) -> Plan {
+        let mut plan = Plan::default();
+        let mut hand = hand.to_vec();
+        while !hand.is_empty() {
+            let turns = self.turns_to_empty(&hand);
+            let (start, stop) = self
+                .valid_ranges(&hand)
+                .into_iter()
+                .find(|&(start, stop)| {
+                    self.turns_to_empty(&without(&hand, start, stop)) == turns - 1
+                })
+                .unwrap();
+            plan.shows.push((start, stop));
+            plan.sets.push(hand.drain(start..stop + 1).collect());
+        }
+        plan
+    }
+
+    /// Returns every optimal decomposition of `hand`. Plans which show the same groups of cards
+    /// in a different order are only listed once.
+    pub fn plans(&mut self, hand: &[i32]) -> Vec<Plan> {
+        // Track the original position of each card, to identify duplicate decompositions
+        let cards: Vec<(usize, i32)> = hand.iter().copied().enumerate().collect();
+        let mut plans = Vec::new();
+        self.collect_plans(
+            &cards,
+            &mut Plan::default(),
+            &mut Vec::new(),
+            &mut HashSet::new(),
+            &mut plans,
+        );
+        plans
+    }
+
+    fn collect_plans(
+        &mut self,
+        cards: &[(usize, i32)],
+        plan: &mut Plan,
+        groups: &mut Vec<Vec<usize>>,
+        seen: &mut HashSet<Vec<Vec<usize>>>,
+        plans: &mut Vec<Plan>,
+    ) {
+        if cards.is_empty() {
+            let mut key = groups.clone();
+            key.sort();
+            if seen.insert(key) {
+                plans.push(plan.clone());
+            }
+            return;
+        }
+
+        let hand: Vec<i32> = cards.iter().map(|card| card.1).collect();
+        let turns = self.turns_to_empty(&hand);
+        for (start, stop) in self.valid_ranges(&hand) {
+            if self.turns_to_empty(&without(&hand, start, stop)) != turns - 1 {
+                continue;
+            }
+            plan.shows.push((start, stop));
+            plan.sets.push(hand[start..stop + 1].to_vec());
+            groups.push(cards[start..stop + 1].iter().map(|card| card.0).collect());
+
+            let rest = without(cards, start, stop);
+            self.collect_plans(&rest, plan, groups, seen, plans);
+
+            plan.shows.pop();
+            plan.sets.pop();
+            groups.pop();
+        }
+    }
+
+    /// Returns every index and orientation to insert `card` into `hand`, best first.
+    /// Insertions are ranked by the turns needed to empty the new hand, then the largest set
+    /// formed with the card, then the net number of sets created.
+    pub fn insertions(&mut self, hand: &[i32], card: &Card) -> Vec<Insertion> {
+        let mut insertions = Vec::new();
+        for index in 0..hand.len() + 1 {
+            for flip in [false, true] {
+                let mut new_hand = hand.to_vec();
+                new_hand.insert(index, if flip { card.1 } else { card.0 });
+                let sets: Vec<usize> = self
+                    .valid_ranges(&new_hand)
+                    .into_iter()
+                    .filter(|&(start, stop)| start <= index && index <= stop)
+                    .map(|(start, stop)| stop + 1 - start)
+                    .collect();
+                // Sets of the old hand which span the gap the card is inserted into
+                let sets_broken = self
+                    .valid_ranges(hand)
+                    .into_iter()
+                    .filter(|&(start, stop)| start < index && index <= stop)
+                    .count();
+                insertions.push(Insertion {
+                    index,
+                    flip,
+                    turns_to_empty: self.turns_to_empty(&new_hand),
+                    largest_set: sets.iter().copied().max().unwrap_or(0),
+                    sets_created: sets.iter().filter(|&&len| len > 1).count(),
+                    sets_broken,
+                });
+            }
+        }
+        insertions.sort_by_key(|insertion| {
+            (
+                insertion.turns_to_empty,
+                Reverse(insertion.largest_set),
+                Reverse(insertion.net_sets()),
+            )
+        });
+        insertions
+    }
+
+    /// Inclusive ranges of `hand` which form valid sets.
+    fn valid_ranges(&self, hand: &[i32]) -> Vec<(usize, usize)> {
+        (0..hand.len())
+            .flat_map(|start| (start..hand.len()).map(move |stop| (start, stop)))
+            .filter(|&(start, stop)| self.set_map.contains_key(&hand[start..stop + 1]))
+            .collect()
+    }
+}
+
+/// Cache key shared by a hand and its reverse.
+fn canonical(hand: &[i32]) -> Vec<i32> {
+    if hand.iter().rev().lt(hand.iter()) {
+        hand.iter().rev().copied().collect()
+    } else {
+        hand.to_vec()
+    }
+}
+
+/// Copy of `hand` with the inclusive range removed.
+fn without<T: Clone>(hand: &[T], start: usize, stop: usize) -> Vec<T> {
+    let mut rest = hand[..start].to_vec();
+    rest.extend_from_slice(&hand[stop + 1..]);
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_set_map;
+    use crate::rules::{RuleSet, SetOrdering};
+
+    #[test]
+    fn test_turns_to_empty() {
+        let mut analyser = HandAnalyser::new(default_set_map());
+
+        // Trivial cases
+        assert_eq!(analyser.turns_to_empty(&[]), 0);
+        assert_eq!(analyser.turns_to_empty(&[0]), 1);
+        assert_eq!(analyser.turns_to_empty(&[0, 1, 2]), 1);
+
+        // Fiddly examples
+        assert_eq!(analyser.turns_to_empty(&[0, 1, 0]), 2);
+        assert_eq!(analyser.turns_to_empty(&[1, 3, 5]), 3);
+        assert_eq!(analyser.turns_to_empty(&[1, 3, 1]), 2);
+        assert_eq!(analyser.turns_to_empty(&[1, 3, 3, 1]), 2);
+        assert_eq!(analyser.turns_to_empty(&[1, 3, 5, 7, 1]), 4);
+
+        // Big hands
+        assert_eq!(analyser.turns_to_empty(&[7, 3, 2, 1, 4, 7, 1, 2, 1]), 5);
+
+        // Reversed hands share a cache entry
+        let cached = analyser.cache.len();
+        assert_eq!(analyser.turns_to_empty(&[1, 2, 1, 7, 4, 1, 2, 3, 7]), 5);
+        assert_eq!(analyser.cache.len(), cached);
+    }
+
+    #[test]
+    fn test_plans() {
+        let mut analyser = HandAnalyser::new(default_set_map());
+
+        // Showing the 3s joins the 1s into a flush
+        let plan = analyser.plan(&[1, 3, 3, 1]);
+        assert_eq!(plan.shows, vec![(1, 2), (0, 1)]);
+        assert_eq!(plan.sets, vec![vec![3, 3], vec![1, 1]]);
+        assert_eq!(plan.turns(), 2);
+
+        // Every plan is optimal, valid, and empties the hand
+        let hand = vec![7, 3, 2, 1, 4, 7, 1, 2, 1];
+        let plans = analyser.plans(&hand);
+        assert!(plans.contains(&analyser.plan(&hand)));
+        for plan in &plans {
+            assert_eq!(plan.turns(), 5);
+            let mut remaining = hand.clone();
+            for (&(start, stop), set) in plan.shows.iter().zip(&plan.sets) {
+                assert!(analyser.set_map().contains_key(set));
+                assert_eq!(remaining.drain(start..stop + 1).collect::<Vec<_>>(), *set);
+            }
+            assert!(remaining.is_empty());
+        }
+
+        // Independent sets shown in either order are the same decomposition
+        assert_eq!(analyser.plans(&[1, 1, 5, 5]).len(), 1);
+    }
+
+    #[test]
+    fn test_find_sets() {
+        let set_map = default_set_map();
+        let sets = find_sets(&[3, 4, 5, 5, 5], &set_map);
+        assert_eq!(sets.len(), 11);
+
+        // The flush of three is strongest, and the only set beating the straight of three
+        let strongest = sets.strongest().unwrap();
+        assert_eq!((strongest.start, strongest.stop), (2, 4));
+        assert_eq!(strongest.kind, SetKind::Flush);
+        assert_eq!(strongest.len, 3);
+
+        let straight = set_map[&vec![3, 4, 5]];
+        let beating: Vec<(usize, usize)> = sets
+            .beating(straight)
+            .iter()
+            .map(|set| (set.start, set.stop))
+            .collect();
+        assert_eq!(beating, vec![(2, 4)]);
+        assert_eq!(sets.beating(0).len(), sets.len());
+        assert!(sets
+            .iter()
+            .filter(|set| set.len == 1)
+            .all(|set| set.kind == SetKind::Single));
+    }
+
+    #[test]
+    fn test_compare_sets() {
+        let set_map = default_set_map();
+        let compare = |a: &[i32], b: &[i32]| {
+            (
+                compare_sets(a, b, &set_map),
+                explain_comparison(a, b, &set_map),
+            )
+        };
+
+        assert_eq!(
+            compare(&[1, 1, 1], &[9, 9]),
+            (Ordering::Greater, Reason::Longer)
+        );
+        assert_eq!(
+            compare(&[8, 9], &[1, 1]),
+            (Ordering::Less, Reason::FlushBeatsStraight)
+        );
+        assert_eq!(compare(&[3, 2], &[2, 3]), (Ordering::Equal, Reason::Equal));
+        assert_eq!(
+            compare(&[5], &[4]),
+            (Ordering::Greater, Reason::HigherValue)
+        );
+        assert_eq!(compare(&[0], &[]), (Ordering::Greater, Reason::Empty));
+        assert_eq!(
+            compare(&[1, 3], &[]),
+            (Ordering::Less, Reason::Invalid(vec![1, 3]))
+        );
+        assert_eq!(
+            compare(&[2, 2], &[1, 3]).1.to_string(),
+            "[1, 3] is not a valid set"
+        );
+
+        // The reason follows the ordering of the rules
+        let set_map = RuleSet {
+            ordering: SetOrdering::StraightsHigher,
+            ..Default::default()
+        }
+        .set_map();
+        assert_eq!(
+            compare_sets_with_reason(&[8, 9], &[1, 1], &set_map),
+            (Ordering::Greater, Reason::StraightBeatsFlush)
+        );
+        assert_eq!(
+            compare_sets_with_reason(&[9, 9], &[1, 2], &set_map),
+            (Ordering::Less, Reason::StraightBeatsFlush)
+        );
+    }
+
+    #[test]
+    fn test_insertions() {
+        let mut analyser = HandAnalyser::new(default_set_map());
+
+        // Flipping the card to insert its 3 between the 2 and 4 completes the straight, which
+        // empties the hand in a single show
+        let insertions = analyser.insertions(&[1, 2, 4], &Card::new(7, 3));
+        assert_eq!(insertions.len(), 8);
+        assert_eq!(
+            insertions[0],
+            Insertion {
+                index: 2,
+                flip: true,
+                turns_to_empty: 1,
+                largest_set: 4,
+                sets_created: 5,
+                sets_broken: 0,
+            }
+        );
+        assert!(insertions[1..].iter().all(|i| i.turns_to_empty > 1));
+
+        // Inserting a 7 into the run 1, 2, 3 splits [1, 2] and [1, 2, 3] without creating any
+        // set. The hand still empties in two shows by showing the 7 first, so only the broken
+        // sets rank it below adding the 7 to the end
+        let insertions = analyser.insertions(&[1, 2, 3], &Card::new(7, 9));
+        let split = insertions
+            .iter()
+            .position(|i| (i.index, i.flip) == (1, false))
+            .unwrap();
+        assert_eq!(
+            insertions[split],
+            Insertion {
+                index: 1,
+                flip: false,
+                turns_to_empty: 2,
+                largest_set: 1,
+                sets_created: 0,
+                sets_broken: 2,
+            }
+        );
+        assert_eq!(insertions[split].net_sets(), -2);
+        let end = insertions
+            .iter()
+            .position(|i| (i.index, i.flip) == (3, false))
+            .unwrap();
+        assert!(end < split);
+        assert_eq!(insertions[end].net_sets(), 0);
+    }
+}
@@ -0,0 +1,528 @@
+//! Monte Carlo counterfactual regret minimisation for small rule variants.
+//!
+//! `Trainer` runs external sampling MCCFR, where each iteration samples a deal and traverses
+//! every action of one player while sampling the actions of the others. Information sets are
+//! keyed by the Zobrist hash of the acting player's `GameView`, so positions reached by different
+//! histories which look the same share a strategy. Players therefore forget the order of
+//! earlier actions, which makes this a game of imperfect recall. CFR has no convergence
+//! guarantee under imperfect recall, so the trained strategies are heuristics rather than
+//! equilibria.
+//!
+//! Scouting can keep a round going indefinitely, so play is cut off after a maximum number of
+//! actions. A cut off round is scored as if it had just ended by a hand being emptied.
+//!
+//! Payoffs are each player's score minus the mean score of the others, which sums to zero over
+//! the players.
+
+use crate::rules::RuleSet;
+use crate::{get_valid_actions, Action, GameError, GameState, GameView, SetMap, Strategy};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Payoff for `seat` at the end of a round, or when it is cut off.
+fn payoff(state: &GameState, seat: usize) -> f64 {
+    let scores: Vec<i32> = match state.result() {
+        Some(result) => result.scores,
+        None => state
+            .players
+            .iter()
+            .map(|p| p.score(&state.rules.scoring) - p.hand.len() as i32)
+            .collect(),
+    };
+    let others: i32 = scores.iter().sum::<i32>() - scores[seat];
+    scores[seat] as f64 - others as f64 / (scores.len() - 1) as f64
+}
+
+/// Pick an index with the given probabilities.
+fn sample<R: Rng>(probabilities: &[f64], rng: &mut R) -> usize {
+    let mut target = rng.gen::<f64>() * probabilities.iter().sum::<f64>();
+    for (i, &probability) in probabilities.iter().enumerate() {
+        if target < probability {
+            return i;
+        }
+        target -= probability;
+    }
+    probabilities.len() - 1
+}
+
+/// Probabilities with which a player picks each legal action.
+pub trait Profile {
+    /// Probability of each of `actions`, the legal actions for `view` in the order given by
+    /// `get_valid_actions`.
+    fn probabilities(&mut self, view: &GameView, actions: &[Action]) -> Vec<f64>;
+}
+
+/// Profile of a `Strategy`, which always plays the action it returns. A strategy which resigns
+/// or returns an illegal action is treated as picking uniformly at random.
+pub struct Pure<S>(pub S);
+
+impl<S: Strategy> Profile for Pure<S> {
+    fn probabilities(&mut self, view: &GameView, actions: &[Action]) -> Vec<f64> {
+        let action = self.0.get_action(view);
+        match actions.iter().position(|legal| Some(*legal) == action) {
+            Some(i) => (0..actions.len()).map(|j| (i == j) as u8 as f64).collect(),
+            None => vec![1.0 / actions.len() as f64; actions.len()],
+        }
+    }
+}
+
+/// Regrets and strategy totals for an information set.
+#[derive(Clone, Debug)]
+struct Node {
+    regrets: Vec<f64>,
+    strategy_sum: Vec<f64>,
+}
+
+impl Node {
+    fn new(actions: usize) -> Node {
+        Node {
+            regrets: vec![0.0; actions],
+            strategy_sum: vec![0.0; actions],
+        }
+    }
+
+    /// Current strategy by regret matching: proportional to positive regret, or uniform if no
+    /// action has any.
+    fn strategy(&self) -> Vec<f64> {
+        let positive: Vec<f64> = self.regrets.iter().map(|regret| regret.max(0.0)).collect();
+        let total: f64 = positive.iter().sum();
+        match total > 0.0 {
+            true => positive.iter().map(|regret| regret / total).collect(),
+            false => vec![1.0 / self.regrets.len() as f64; self.regrets.len()],
+        }
+    }
+}
+
+/// External sampling MCCFR over games of a fixed number of players and rules.
+pub struct Trainer {
+    rules: RuleSet,
+    set_map: SetMap,
+    players: usize,
+    max_actions: usize,
+    iterations: usize,
+    rng: StdRng,
+    nodes: HashMap<u64, Node>,
+}
+
+impl Trainer {
+    /// Create a trainer for games of `players` players with the given `rules`, cutting rounds off
+    /// after `max_actions` actions. Returns `GameError::PlayerCount` if the rules can't deal a
+    /// game for that many players.
+    pub fn new(
+        rules: RuleSet,
+        players: usize,
+        max_actions: usize,
+        seed: u64,
+    ) -> Result<Trainer, GameError> {
+        if !rules.supports(players) {
+            return Err(GameError::PlayerCount(players));
+        }
+        Ok(Trainer {
+            set_map: rules.set_map(),
+            rules,
+            players,
+            max_actions,
+            iterations: 0,
+            rng: StdRng::seed_from_u64(seed),
+            nodes: HashMap::new(),
+        })
+    }
+
+    /// Number of iterations trained so far.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Number of information sets visited so far.
+    pub fn info_sets(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Run `iterations` iterations. Each samples one deal, which is traversed once for each
+    /// player.
+    pub fn train(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            let seed = self.rng.gen();
+            let mut state =
+                GameState::with_rules(self.players, self.rules.clone(), Some(seed)).unwrap();
+            for traverser in 0..self.players {
+                self.traverse(&mut state, traverser, self.max_actions);
+            }
+            self.iterations += 1;
+        }
+    }
+
+    /// Expected payoff for `traverser` from `state`, updating regrets at the traverser's
+    /// information sets and strategy totals at everyone else's.
+    fn traverse(&mut self, state: &mut GameState, traverser: usize, depth: usize) -> f64 {
+        if state.round_end.is_some() || depth == 0 {
+            return payoff(state, traverser);
+        }
+        let view = state.as_view();
+        let actions = get_valid_actions(&view, &self.set_map);
+        let key = view.zobrist();
+        let node = self
+            .nodes
+            .entry(key)
+            .or_insert_with(|| Node::new(actions.len()));
+        if node.regrets.len() != actions.len() {
+            // A hash collision between information sets, which keeps the newest
+            *node = Node::new(actions.len());
+        }
+        let strategy = node.strategy();
+
+        if state.turn != traverser {
+            for (sum, probability) in node.strategy_sum.iter_mut().zip(&strategy) {
+                *sum += probability;
+            }
+            let action = actions[sample(&strategy, &mut self.rng)];
+            let undo = state.apply(&action);
+            let value = self.traverse(state, traverser, depth - 1);
+            state.undo(undo);
+            return value;
+        }
+
+        let values: Vec<f64> = actions
+            .iter()
+            .map(|action| {
+                let undo = state.apply(action);
+                let value = self.traverse(state, traverser, depth - 1);
+                state.undo(undo);
+                value
+            })
+            .collect();
+        let expected: f64 = values.iter().zip(&strategy).map(|(v, p)| v * p).sum();
+        if let Some(node) = self.nodes.get_mut(&key) {
+            for (regret, value) in node.regrets.iter_mut().zip(&values) {
+                *regret += value - expected;
+            }
+        }
+        expected
+    }
+
+    /// The average strategy over all iterations. Information sets forget the order of earlier
+    /// actions, so this isn't guaranteed to approach an equilibrium, even in two-player games.
+    pub fn average_strategy(&self) -> Policy {
+        let table = self
+            .nodes
+            .iter()
+            .map(|(&key, node)| {
+                let total: f64 = node.strategy_sum.iter().sum();
+                let probabilities = match total > 0.0 {
+                    true => node.strategy_sum.iter().map(|sum| sum / total).collect(),
+                    false => vec![1.0 / node.strategy_sum.len() as f64; node.strategy_sum.len()],
+                };
+                (key, probabilities)
+            })
+            .collect();
+        Policy { table }
+    }
+
+    /// Heuristic estimate of the exploitability of the average strategy over `deals` sampled
+    /// deals, see `exploitability`.
+    pub fn exploitability(&mut self, deals: usize) -> f64 {
+        let seed = self.rng.gen();
+        let rules = self.rules.clone();
+        exploitability(
+            &mut self.average_strategy(),
+            &rules,
+            self.players,
+            self.max_actions,
+            deals,
+            seed,
+        )
+    }
+}
+
+/// Action probabilities for each information set, keyed by the Zobrist hash of the view.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Policy {
+    table: HashMap<u64, Vec<f64>>,
+}
+
+impl Policy {
+    /// Number of information sets in the policy.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Probabilities for the legal actions of `view`, in the order given by `get_valid_actions`,
+    /// or `None` if the view wasn't visited in training.
+    pub fn get(&self, view: &GameView) -> Option<&[f64]> {
+        self.table.get(&view.zobrist()).map(Vec::as_slice)
+    }
+
+    /// Save the policy as text, with one line per information set: its key in hex, then the
+    /// probability of each action.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut keys: Vec<&u64> = self.table.keys().collect();
+        keys.sort();
+        let mut text = String::new();
+        for key in keys {
+            let probabilities: Vec<String> =
+                self.table[key].iter().map(|p| p.to_string()).collect();
+            text.push_str(&format!("{:016x} {}\n", key, probabilities.join(" ")));
+        }
+        fs::write(path, text)
+    }
+
+    /// Load a policy written by `save`.
+    pub fn load(path: &Path) -> io::Result<Policy> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+        let text = fs::read_to_string(path)?;
+        let table = text
+            .lines()
+            .map(|line| {
+                let mut fields = line.split_whitespace();
+                let key = fields
+                    .next()
+                    .and_then(|key| u64::from_str_radix(key, 16).ok())
+                    .ok_or_else(|| invalid("policy has an invalid key"))?;
+                let probabilities = fields
+                    .map(|p| p.parse())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| invalid("policy has an invalid probability"))?;
+                Ok((key, probabilities))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Policy { table })
+    }
+}
+
+impl Profile for Policy {
+    /// Views which weren't visited in training are played uniformly at random.
+    fn probabilities(&mut self, view: &GameView, actions: &[Action]) -> Vec<f64> {
+        match self.get(view) {
+            Some(probabilities) if probabilities.len() == actions.len() => probabilities.to_vec(),
+            _ => vec![1.0 / actions.len() as f64; actions.len()],
+        }
+    }
+}
+
+/// Strategy which samples actions from a `Policy`.
+pub struct PolicyStrategy {
+    policy: Policy,
+    set_map: SetMap,
+    rng: StdRng,
+}
+
+impl PolicyStrategy {
+    /// Create a strategy for games ranked by `set_map`, which must match the rules the policy
+    /// was trained with.
+    pub fn new(policy: Policy, set_map: SetMap, seed: u64) -> PolicyStrategy {
+        PolicyStrategy {
+            policy,
+            set_map,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for PolicyStrategy {
+    fn get_action(&mut self, view: &GameView) -> Option<Action> {
+        let actions = get_valid_actions(view, &self.set_map);
+        if actions.is_empty() {
+            return None;
+        }
+        let probabilities = self.policy.probabilities(view, &actions);
+        Some(actions[sample(&probabilities, &mut self.rng)])
+    }
+}
+
+/// Most passes over the deals made to improve a best response.
+pub const MAX_PASSES: usize = 16;
+
+/// Best response search over a fixed set of deals.
+struct BestResponse<'a, P> {
+    profile: &'a mut P,
+    set_map: SetMap,
+    seat: usize,
+    /// Action played at each of the responder's information sets
+    choices: HashMap<u64, usize>,
+    /// Value of each action at each of the responder's information sets, weighted by the
+    /// probability of the other players reaching it
+    values: HashMap<u64, Vec<f64>>,
+}
+
+impl<P: Profile> BestResponse<'_, P> {
+    /// Value for the responder of `state`, with the other players following the profile. If
+    /// `respond` is false the responder follows the profile too.
+    fn value(&mut self, state: &mut GameState, reach: f64, depth: usize, respond: bool) -> f64 {
+        if state.round_end.is_some() || depth == 0 {
+            return payoff(state, self.seat);
+        }
+        let view = state.as_view();
+        let actions = get_valid_actions(&view, &self.set_map);
+
+        if respond && state.turn == self.seat {
+            let key = view.zobrist();
+            let values: Vec<f64> = actions
+                .iter()
+                .map(|action| {
+                    let undo = state.apply(action);
+                    let value = self.value(state, reach, depth - 1, respond);
+                    state.undo(undo);
+                    value
+                })
+                .collect();
+            let totals = self
+                .values
+                .entry(key)
+                .or_insert_with(|| vec![0.0; actions.len()]);
+            if totals.len() == actions.len() {
+                for (total, value) in totals.iter_mut().zip(&values) {
+                    *total += reach * value;
+                }
+            }
+            let choice = self.choices.get(&key).copied().unwrap_or(0);
+            return values[choice.min(values.len() - 1)];
+        }
+
+        let probabilities = self.profile.probabilities(&view, &actions);
+        let mut value = 0.0;
+        for (action, probability) in actions.iter().zip(probabilities) {
+            if probability > 0.0 {
+                let undo = state.apply(action);
+                value += probability * self.value(state, reach * probability, depth - 1, respond);
+                state.undo(undo);
+            }
+        }
+        value
+    }
+}
+
+/// Heuristic estimate of how much a player could gain on average by deviating from `profile`,
+/// when every other player follows it.
+///
+/// `deals` deals are sampled from `seed`, and each seat's response is found over those deals
+/// by repeatedly switching every information set to its best action, until no choice changes
+/// or after `MAX_PASSES` passes. This iterated response is only approximately best: it may
+/// settle on a local optimum, and with information sets keyed only by the view hash there is
+/// no guarantee it converges at all. It can also exploit the sampled deals, so estimates from
+/// few deals are biased upwards. Every line is searched, so this is only practical for very
+/// small games.
+///
+/// Returns the mean over seats of the response's payoff minus the profile's own payoff. This
+/// is neither a bound on nor the exact value of the exploitability, but is useful for comparing
+/// profiles on the same deals.
+pub fn exploitability<P: Profile>(
+    profile: &mut P,
+    rules: &RuleSet,
+    players: usize,
+    max_actions: usize,
+    deals: usize,
+    seed: u64,
+) -> f64 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let games: Vec<GameState> = (0..deals.max(1))
+        .filter_map(|_| GameState::with_rules(players, rules.clone(), Some(rng.gen())).ok())
+        .collect();
+    if games.is_empty() {
+        return 0.0;
+    }
+
+    let mut gain = 0.0;
+    for seat in 0..players {
+        let mut response = BestResponse {
+            profile: &mut *profile,
+            set_map: rules.set_map(),
+            seat,
+            choices: HashMap::new(),
+            values: HashMap::new(),
+        };
+        let mean = |response: &mut BestResponse<P>, respond| {
+            games
+                .iter()
+                .map(|game| response.value(&mut game.clone(), 1.0, max_actions, respond))
+                .sum::<f64>()
+                / games.len() as f64
+        };
+        let own = mean(&mut response, false);
+        let mut best = mean(&mut response, true);
+        for _ in 0..MAX_PASSES {
+            let choices: HashMap<u64, usize> = response
+                .values
+                .drain()
+                .map(|(key, values)| {
+                    let best =
+                        (0..values.len()).fold(0, |best, i| match values[i] > values[best] {
+                            true => i,
+                            false => best,
+                        });
+                    (key, best)
+                })
+                .collect();
+            if choices == response.choices {
+                break;
+            }
+            response.choices = choices;
+            best = best.max(mean(&mut response, true));
+        }
+        gain += best - own;
+    }
+    gain / players as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Greedy;
+    use crate::{run_with, GameOptions, GameState};
+    use std::env;
+
+    #[test]
+    fn test_cfr() {
+        let rules = RuleSet::mini(3, 2);
+        assert!(Trainer::new(rules.clone(), 4, 6, 0).is_err());
+
+        let mut trainer = Trainer::new(rules.clone(), 2, 3, 0).unwrap();
+        trainer.train(500);
+        assert_eq!(trainer.iterations(), 500);
+        assert!(trainer.info_sets() > 0);
+        let policy = trainer.average_strategy();
+
+        // The average strategy is a distribution at every information set
+        let game = GameState::with_rules(2, rules.clone(), Some(1)).unwrap();
+        let probabilities = policy.get(&game.as_view()).unwrap();
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        // Training makes the strategy harder to exploit than playing at random
+        let trained = exploitability(&mut policy.clone(), &rules, 2, 3, 5, 7);
+        let random = exploitability(&mut Policy::default(), &rules, 2, 3, 5, 7);
+        assert!(trained < random, "{} >= {}", trained, random);
+        assert!(trainer.exploitability(2) >= 0.0);
+
+        // Deterministic strategies can be measured too
+        let greedy = exploitability(&mut Pure(Greedy(rules.set_map())), &rules, 2, 3, 5, 7);
+        assert!(greedy >= 0.0);
+
+        // The policy survives a round trip to disk, and can be played as a strategy
+        let path = env::temp_dir().join(format!("scout-cfr-{}.txt", std::process::id()));
+        policy.save(&path).unwrap();
+        let loaded = Policy::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), policy.len());
+        assert_eq!(
+            loaded.get(&game.as_view()).map(<[f64]>::len),
+            Some(probabilities.len())
+        );
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(PolicyStrategy::new(loaded, rules.set_map(), 0)),
+            Box::new(Greedy(rules.set_map())),
+        ];
+        let options = GameOptions {
+            rules: Some(rules),
+            ..Default::default()
+        };
+        assert!(run_with(&mut strategies, options).is_ok());
+    }
+}
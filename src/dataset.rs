@@ -0,0 +1,384 @@
+//! Self-play dataset generation, with one row per decision.
+//!
+//! Rows are written to shards in either CSV or a simple binary format. Views are encoded with
+//! `env::encode_view`, and actions are indexes into an `env::ActionSpace`.
+
+use crate::env::{encode_view, encoded_len, ActionSpace, MAX_PLAYERS};
+use crate::rules::RuleSet;
+use crate::{
+    get_valid_actions, run_with, Action, GameError, GameOptions, GameResult, GameState, Observer,
+    SetMap, Strategy,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes at the start of each binary shard.
+pub const BINARY_MAGIC: &[u8; 8] = b"SCOUTDS1";
+
+/// Output format for shards.
+///
+/// Legal action masks are sparse, so both formats store the indexes of legal actions rather
+/// than the full mask.
+///
+/// CSV shards have a header, then columns `seed, seat, turn, action, outcome`, `MAX_PLAYERS`
+/// score columns, the encoded view, and the legal indexes separated by spaces.
+///
+/// Binary shards start with `BINARY_MAGIC` then the number of features as a `u32`. Each row is
+/// then `seed: u64, seat: u32, turn: u32, action: u32, outcome: f32`, `MAX_PLAYERS` scores as
+/// `i32`, the features as `f32`, and the number of legal indexes as `u32` followed by the
+/// indexes as `u32`. All values are little endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Binary,
+}
+
+/// Options for `self_play`.
+#[derive(Clone, Debug)]
+pub struct DatasetConfig {
+    /// Number of games to play
+    pub games: usize,
+    /// Seed for the first game's deal, with each later game using the next seed
+    pub seed: u64,
+    /// Number of games written to each shard
+    pub games_per_shard: usize,
+    pub format: Format,
+    /// Largest hand that can be encoded, see `ActionSpace`
+    pub max_hand: usize,
+    /// Rules to play with, or `None` for the official rules for the number of players
+    pub rules: Option<RuleSet>,
+}
+
+impl Default for DatasetConfig {
+    fn default() -> Self {
+        DatasetConfig {
+            games: 1000,
+            seed: 0,
+            games_per_shard: 100,
+            format: Format::Csv,
+            max_hand: 24,
+            rules: None,
+        }
+    }
+}
+
+/// A single decision, seen from the deciding player's seat.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    /// Seed of the game's deal
+    pub seed: u64,
+    pub seat: usize,
+    /// Number of actions taken before this one
+    pub turn: usize,
+    /// The deciding player's view, encoded by `encode_view`
+    pub features: Vec<f32>,
+    /// Indexes of the legal actions
+    pub legal: Vec<usize>,
+    /// Index of the chosen action
+    pub action: usize,
+    /// 1 for a sole win, 0 for a shared win and -1 for a loss
+    pub outcome: f32,
+    /// Final scores, rotated so the deciding player is first (as in `GameView`)
+    pub scores: Vec<i32>,
+}
+
+/// Summary of a `self_play` run.
+#[derive(Clone, Debug, Default)]
+pub struct Dataset {
+    pub shards: Vec<PathBuf>,
+    pub rows: usize,
+    /// Seeds of games discarded because a strategy resigned, panicked or played illegally
+    pub halted: Vec<u64>,
+}
+
+/// Play `config.games` games with `strategies`, writing every decision to shards in `dir`.
+///
+/// Deals are deterministic given `config.seed`, though the rows are only reproducible if the
+/// strategies are too. Games which a strategy fails to finish are left out of the dataset.
+pub fn self_play(
+    strategies: &mut [Box<dyn Strategy>],
+    config: &DatasetConfig,
+    dir: &Path,
+) -> io::Result<Dataset> {
+    let n = strategies.len();
+    let rules = match &config.rules {
+        Some(rules) => rules.clone(),
+        None => RuleSet::official(n).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                GameError::PlayerCount(n).to_string(),
+            )
+        })?,
+    };
+    let set_map = rules.set_map();
+    let mut dataset = Dataset::default();
+    let shard_size = config.games_per_shard.max(1);
+    for (shard, first) in (0..config.games).step_by(shard_size).enumerate() {
+        let extension = match config.format {
+            Format::Csv => "csv",
+            Format::Binary => "bin",
+        };
+        let path = dir.join(format!("shard-{:05}.{}", shard, extension));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        write_header(&mut writer, config)?;
+        for game in first..config.games.min(first + shard_size) {
+            let seed = config.seed + game as u64;
+            match play_game(strategies, seed, &rules, &set_map, config.max_hand)? {
+                Some(rows) => {
+                    for row in &rows {
+                        write_row(&mut writer, row, config.format)?;
+                    }
+                    dataset.rows += rows.len();
+                }
+                None => dataset.halted.push(seed),
+            }
+        }
+        writer.flush()?;
+        dataset.shards.push(path);
+    }
+    Ok(dataset)
+}
+
+/// Observer which records a row for every decision of a game.
+struct Recorder<'a> {
+    seed: u64,
+    set_map: &'a SetMap,
+    space: ActionSpace,
+    max_hand: usize,
+    rows: Vec<Row>,
+    /// The first action which couldn't be encoded
+    error: Option<io::Error>,
+}
+
+impl Recorder<'_> {
+    fn index(&self, action: &Action) -> io::Result<usize> {
+        self.space.index(action).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} doesn't fit in a hand of {} cards",
+                    action, self.max_hand
+                ),
+            )
+        })
+    }
+}
+
+impl Observer for Recorder<'_> {
+    fn on_action(&mut self, game: &GameState, seat: usize, action: &Action) {
+        if self.error.is_some() {
+            return;
+        }
+        let view = game.as_view();
+        let legal = get_valid_actions(&view, self.set_map);
+        let legal = legal.iter().map(|action| self.index(action)).collect();
+        match (legal, self.index(action)) {
+            (Ok(legal), Ok(action)) => self.rows.push(Row {
+                seed: self.seed,
+                seat,
+                turn: game.turns,
+                features: encode_view(&view, self.max_hand),
+                legal,
+                action,
+                outcome: 0.0,
+                scores: Vec::new(),
+            }),
+            (Err(err), _) | (_, Err(err)) => self.error = Some(err),
+        }
+    }
+
+    fn on_result(&mut self, result: &GameResult) {
+        let n_players = result.scores.len();
+        for row in &mut self.rows {
+            row.outcome = match (result.winners.contains(&row.seat), result.winners.len()) {
+                (true, 1) => 1.0,
+                (true, _) => 0.0,
+                (false, _) => -1.0,
+            };
+            row.scores = (0..n_players)
+                .map(|i| result.scores[(row.seat + i) % n_players])
+                .collect();
+        }
+    }
+}
+
+/// Play one game, returning its rows or `None` if it was halted.
+fn play_game(
+    strategies: &mut [Box<dyn Strategy>],
+    seed: u64,
+    rules: &RuleSet,
+    set_map: &SetMap,
+    max_hand: usize,
+) -> io::Result<Option<Vec<Row>>> {
+    let mut recorder = Recorder {
+        seed,
+        set_map,
+        space: ActionSpace::new(max_hand),
+        max_hand,
+        rows: Vec::new(),
+        error: None,
+    };
+    let options = GameOptions {
+        rules: Some(rules.clone()),
+        seed: Some(seed),
+        observer: Some(&mut recorder),
+        ..Default::default()
+    };
+    let result = run_with(strategies, options);
+    if let Some(err) = recorder.error {
+        return Err(err);
+    }
+    match result {
+        Ok(_) => Ok(Some(recorder.rows)),
+        Err(err) if err.halted().is_some() => Ok(None),
+        Err(err) => Err(io::Error::new(io::ErrorKind::InvalidInput, err.to_string())),
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W, config: &DatasetConfig) -> io::Result<()> {
+    let n_features = encoded_len(config.max_hand);
+    match config.format {
+        Format::Csv => {
+            let mut columns: Vec<String> = ["seed", "seat", "turn", "action", "outcome"]
+                .iter()
+                .map(|column| column.to_string())
+                .collect();
+            columns.extend((0..MAX_PLAYERS).map(|i| format!("score{}", i)));
+            columns.extend((0..n_features).map(|i| format!("f{}", i)));
+            columns.push("legal".to_owned());
+            writeln!(writer, "{}", columns.join(","))
+        }
+        Format::Binary => {
+            writer.write_all(BINARY_MAGIC)?;
+            writer.write_all(&(n_features as u32).to_le_bytes())
+        }
+    }
+}
+
+fn write_row<W: Write>(writer: &mut W, row: &Row, format: Format) -> io::Result<()> {
+    let mut scores = row.scores.clone();
+    scores.resize(MAX_PLAYERS, 0);
+    match format {
+        Format::Csv => {
+            let mut fields = vec![
+                row.seed.to_string(),
+                row.seat.to_string(),
+                row.turn.to_string(),
+                row.action.to_string(),
+                row.outcome.to_string(),
+            ];
+            fields.extend(scores.iter().map(|score| score.to_string()));
+            fields.extend(row.features.iter().map(|feature| feature.to_string()));
+            let legal: Vec<String> = row.legal.iter().map(|index| index.to_string()).collect();
+            fields.push(legal.join(" "));
+            writeln!(writer, "{}", fields.join(","))
+        }
+        Format::Binary => {
+            writer.write_all(&row.seed.to_le_bytes())?;
+            for value in [row.seat, row.turn, row.action] {
+                writer.write_all(&(value as u32).to_le_bytes())?;
+            }
+            writer.write_all(&row.outcome.to_le_bytes())?;
+            for score in scores {
+                writer.write_all(&score.to_le_bytes())?;
+            }
+            for feature in &row.features {
+                writer.write_all(&feature.to_le_bytes())?;
+            }
+            writer.write_all(&(row.legal.len() as u32).to_le_bytes())?;
+            for &index in &row.legal {
+                writer.write_all(&(index as u32).to_le_bytes())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_set_map;
+    use crate::tests::Greedy;
+    use std::fs;
+
+    /// Decode every row of a binary shard into the fields of the matching CSV row
+    fn decode_binary(bytes: &[u8]) -> Vec<Vec<String>> {
+        let word = |offset: &mut usize| {
+            *offset += 4;
+            <[u8; 4]>::try_from(&bytes[*offset - 4..*offset]).unwrap()
+        };
+        let n_features = u32::from_le_bytes(word(&mut 8)) as usize;
+        let mut offset = 12;
+        let mut rows = Vec::new();
+        while offset < bytes.len() {
+            offset += 8;
+            let seed = <[u8; 8]>::try_from(&bytes[offset - 8..offset]).unwrap();
+            let mut fields = vec![u64::from_le_bytes(seed).to_string()];
+            for _ in 0..3 {
+                fields.push(u32::from_le_bytes(word(&mut offset)).to_string());
+            }
+            fields.push(f32::from_le_bytes(word(&mut offset)).to_string());
+            for _ in 0..MAX_PLAYERS {
+                fields.push(i32::from_le_bytes(word(&mut offset)).to_string());
+            }
+            for _ in 0..n_features {
+                fields.push(f32::from_le_bytes(word(&mut offset)).to_string());
+            }
+            let legal: Vec<String> = (0..u32::from_le_bytes(word(&mut offset)))
+                .map(|_| u32::from_le_bytes(word(&mut offset)).to_string())
+                .collect();
+            fields.push(legal.join(" "));
+            rows.push(fields);
+        }
+        rows
+    }
+
+    #[test]
+    fn test_self_play() {
+        let dir = std::env::temp_dir().join(format!("scout-dataset-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut strategies: Vec<Box<dyn Strategy>> = (0..3)
+            .map(|_| Box::new(Greedy(default_set_map())) as Box<dyn Strategy>)
+            .collect();
+        let mut config = DatasetConfig {
+            games: 3,
+            seed: 11,
+            games_per_shard: 2,
+            ..Default::default()
+        };
+
+        // Games are split across shards, with one line per row and a header
+        let dataset = self_play(&mut strategies, &config, &dir).unwrap();
+        assert_eq!(dataset.shards.len(), 2);
+        assert!(dataset.halted.is_empty());
+        let csv: Vec<String> = dataset
+            .shards
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        let lines: usize = csv.iter().map(|shard| shard.lines().count() - 1).sum();
+        assert_eq!(lines, dataset.rows);
+
+        // The same seed gives the same dataset
+        let again = self_play(&mut strategies, &config, &dir).unwrap();
+        assert_eq!(fs::read_to_string(&again.shards[0]).unwrap(), csv[0]);
+
+        // Binary shards hold the same rows
+        config.format = Format::Binary;
+        let binary = self_play(&mut strategies, &config, &dir).unwrap();
+        assert_eq!(binary.rows, dataset.rows);
+        for (path, csv) in binary.shards.iter().zip(&csv) {
+            let bytes = fs::read(path).unwrap();
+            assert_eq!(&bytes[..8], BINARY_MAGIC);
+            assert_eq!(bytes[8..12], (encoded_len(24) as u32).to_le_bytes());
+            let rows: Vec<String> = decode_binary(&bytes)
+                .iter()
+                .map(|fields| fields.join(","))
+                .collect();
+            assert_eq!(rows, csv.lines().skip(1).collect::<Vec<_>>());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+//! Duplicate play, which takes card luck out of comparisons between strategies.
+//!
+//! Each deal is played once for every rotation of the seating, so every strategy plays every
+//! seat's hand. Strategies are then scored relative to each other on identical cards.
+
+use crate::rules::RuleSet;
+use crate::{get_action_isolated, play, Failover, GameError, GameResult, GameState, Strategy};
+
+/// Results of playing one deal in every seating.
+#[derive(Clone, Debug)]
+pub struct DuplicateDeal {
+    /// Seed the deck was shuffled from
+    pub seed: u64,
+    /// Result of each rotation. In rotation `r`, strategy `i` sits in seat `(i + r) % n`.
+    pub results: Vec<GameResult>,
+    /// Score of each strategy in each rotation, indexed by strategy then rotation
+    pub scores: Vec<Vec<i32>>,
+    /// Score of each strategy minus the mean score of the others, averaged over rotations. These
+    /// sum to zero.
+    pub relative: Vec<f64>,
+}
+
+/// Play the deal shuffled from `seed` under `rules` once for each rotation of `strategies`.
+///
+/// Returns the first `GameError` if any rotation is halted.
+pub fn run_duplicate(
+    strategies: &mut [Box<dyn Strategy>],
+    rules: &RuleSet,
+    seed: u64,
+) -> Result<DuplicateDeal, GameError> {
+    let n = strategies.len();
+    let mut results = Vec::with_capacity(n);
+    for rotation in 0..n {
+        let game = GameState::with_rules(n, rules.clone(), Some(seed))?;
+        let result = play(game, None, &mut Failover::Forfeit, &mut (), |seat, view| {
+            get_action_isolated(strategies[(seat + n - rotation) % n].as_mut(), view)
+        })?;
+        results.push(result);
+    }
+
+    let scores: Vec<Vec<i32>> = (0..n)
+        .map(|i| {
+            results
+                .iter()
+                .enumerate()
+                .map(|(rotation, result)| result.scores[(i + rotation) % n])
+                .collect()
+        })
+        .collect();
+    let relative = (0..n)
+        .map(|i| {
+            let total: f64 = (0..n)
+                .map(|rotation| {
+                    let others: i32 = (0..n)
+                        .filter(|&j| j != i)
+                        .map(|j| scores[j][rotation])
+                        .sum();
+                    scores[i][rotation] as f64 - others as f64 / (n - 1) as f64
+                })
+                .sum();
+            total / n as f64
+        })
+        .collect();
+    Ok(DuplicateDeal {
+        seed,
+        results,
+        scores,
+        relative,
+    })
+}
+
+/// Relative scores of each strategy over a number of duplicate deals.
+#[derive(Clone, Debug)]
+pub struct DuplicateSummary {
+    pub deals: usize,
+    /// Mean of each strategy's `DuplicateDeal::relative` score
+    pub mean: Vec<f64>,
+    /// Standard error of each mean, which is zero for fewer than two deals
+    pub std_error: Vec<f64>,
+}
+
+impl DuplicateSummary {
+    /// Number of standard errors the mean of strategy `i` is from zero. Values beyond about 2
+    /// suggest a real difference between the strategies.
+    pub fn z_score(&self, i: usize) -> f64 {
+        match self.std_error[i] > 0.0 {
+            true => self.mean[i] / self.std_error[i],
+            false => 0.0,
+        }
+    }
+}
+
+/// Play `deals` duplicate deals under `rules`, shuffled from consecutive seeds starting at
+/// `seed`.
+pub fn compare(
+    strategies: &mut [Box<dyn Strategy>],
+    rules: &RuleSet,
+    deals: usize,
+    seed: u64,
+) -> Result<DuplicateSummary, GameError> {
+    let n = strategies.len();
+    let mut relative = vec![Vec::with_capacity(deals); n];
+    for deal in 0..deals {
+        let result = run_duplicate(strategies, rules, seed + deal as u64)?;
+        for (scores, score) in relative.iter_mut().zip(result.relative) {
+            scores.push(score);
+        }
+    }
+
+    let mean: Vec<f64> = relative
+        .iter()
+        .map(|scores| scores.iter().sum::<f64>() / deals.max(1) as f64)
+        .collect();
+    let std_error = relative
+        .iter()
+        .zip(&mean)
+        .map(|(scores, mean)| match deals {
+            0 | 1 => 0.0,
+            _ => {
+                let variance = scores
+                    .iter()
+                    .map(|score| (score - mean).powi(2))
+                    .sum::<f64>()
+                    / (deals - 1) as f64;
+                (variance / deals as f64).sqrt()
+            }
+        })
+        .collect();
+    Ok(DuplicateSummary {
+        deals,
+        mean,
+        std_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::{GreedyStrategy, ScoreMargin};
+    use crate::tests::Greedy;
+    use crate::{default_set_map, run_with, GameOptions};
+
+    #[test]
+    fn test_duplicate() {
+        // Identical strategies play identical games from each seat, so none is ahead
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        let rules = RuleSet::default();
+        let deal = run_duplicate(&mut strategies, &rules, 4).unwrap();
+        assert_eq!(deal.results.len(), 3);
+        assert!(deal.relative.iter().all(|&score| score.abs() < 1e-9));
+
+        // The first rotation is the seeded game
+        let options = GameOptions {
+            seed: Some(4),
+            ..Default::default()
+        };
+        let seeded = run_with(&mut strategies, options).unwrap();
+        assert_eq!(deal.results[0].scores, seeded.scores);
+
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(GreedyStrategy::new(ScoreMargin, default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        let rules = RuleSet::two_player();
+        let deal = run_duplicate(&mut strategies, &rules, 0).unwrap();
+        for rotation in 0..2 {
+            assert_eq!(
+                deal.scores[0][rotation],
+                deal.results[rotation].scores[rotation]
+            );
+        }
+        assert!(deal.relative.iter().sum::<f64>().abs() < 1e-9);
+
+        let summary = compare(&mut strategies, &rules, 4, 0).unwrap();
+        assert_eq!(summary.deals, 4);
+        assert!((summary.mean[0] + summary.mean[1]).abs() < 1e-9);
+        assert!(summary.std_error.iter().all(|&error| error >= 0.0));
+
+        // Smaller variants can be compared too
+        let rules = RuleSet::mini(4, 4);
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(GreedyStrategy::new(ScoreMargin, rules.set_map())),
+            Box::new(Greedy(rules.set_map())),
+        ];
+        let deal = run_duplicate(&mut strategies, &rules, 0).unwrap();
+        assert!(deal.results.iter().all(|result| result.scores.len() == 2));
+        assert!(compare(&mut strategies, &rules, 2, 0).is_ok());
+    }
+}
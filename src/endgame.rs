@@ -0,0 +1,249 @@
+//! Exact solver for positions where every hand is known.
+//!
+//! The whole game tree is searched from a `GameState`, caching solved positions in a
+//! transposition table. This is only practical near the end of a round, when few cards are left.
+
+use crate::evaluation::final_value;
+use crate::search::Backup;
+use crate::{get_valid_actions, zobrist, Action, GameState, SetMap};
+use std::collections::HashMap;
+
+/// Optimal play from a position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    /// Value of the final position for each seat (see `evaluation::final_value`), in the same
+    /// order as the players of the solved `GameState`
+    pub values: Vec<f64>,
+    /// Actions played from the position until the round ends
+    pub principal_variation: Vec<Action>,
+    /// Whether every line ended within the solver's horizon. Otherwise play is only optimal
+    /// among the lines which did.
+    pub exact: bool,
+}
+
+/// Position solved without reaching the horizon, with the best action to play from it.
+struct Entry {
+    values: Vec<f64>,
+    best: Option<Action>,
+}
+
+/// Best line from a position, with its actions in reverse order.
+struct Line {
+    values: Vec<f64>,
+    actions: Vec<Action>,
+    complete: bool,
+}
+
+/// Exhaustive search with a transposition table.
+///
+/// With `Backup::MaxN` each player maximises their own value. With `Backup::Paranoid` the
+/// player to move at the root maximises their value, and every other player minimises it.
+///
+/// Scouting can keep a round going indefinitely, so lines are cut off after `max_depth` actions.
+pub struct Solver {
+    set_map: SetMap,
+    backup: Backup,
+    max_depth: usize,
+    max_nodes: usize,
+    nodes: usize,
+    table: HashMap<u64, Entry>,
+}
+
+impl Solver {
+    /// Create a solver which searches up to `max_depth` actions ahead, and gives up after
+    /// visiting `max_nodes` positions in a single solve.
+    pub fn new(set_map: SetMap, backup: Backup, max_depth: usize, max_nodes: usize) -> Solver {
+        Solver {
+            set_map,
+            backup,
+            max_depth,
+            max_nodes,
+            nodes: 0,
+            table: HashMap::new(),
+        }
+    }
+
+    /// Number of positions visited by the last call to `solve`.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Solve the position in `state`. Returns `None` if no line ends within the horizon, or
+    /// if the position is too large.
+    ///
+    /// Exactly solved positions are kept between calls, so solving positions from the same
+    /// round is cheaper after the first.
+    pub fn solve(&mut self, state: &GameState) -> Option<Solution> {
+        self.nodes = 0;
+        let root = state.turn;
+        let line = self.search(&mut state.clone(), root, self.max_depth)?;
+        if self.nodes > self.max_nodes {
+            return None;
+        }
+        Some(Solution {
+            values: line.values,
+            principal_variation: line.actions.into_iter().rev().collect(),
+            exact: line.complete,
+        })
+    }
+
+    /// Key for `state` in the table. Paranoid values depend on the root player, so they are
+    /// stored separately.
+    fn key(&self, state: &GameState, root: usize) -> u64 {
+        match self.backup {
+            Backup::MaxN => state.zobrist(),
+            Backup::Paranoid => state.zobrist() ^ zobrist::root(root),
+        }
+    }
+
+    /// Best line from `state` ending within `depth` actions, or `None` if there isn't one.
+    fn search(&mut self, state: &mut GameState, root: usize, depth: usize) -> Option<Line> {
+        let key = self.key(state, root);
+        if let Some(entry) = self.table.get(&key) {
+            return Some(Line {
+                values: entry.values.clone(),
+                actions: self.follow(state, root),
+                complete: true,
+            });
+        }
+        self.nodes += 1;
+        if self.nodes > self.max_nodes {
+            return None;
+        }
+
+        let line = if state.round_end.is_some() {
+            Line {
+                values: (0..state.game_size)
+                    .map(|seat| final_value(&state.view_for(seat)).unwrap())
+                    .collect(),
+                actions: Vec::new(),
+                complete: true,
+            }
+        } else {
+            if depth == 0 {
+                return None;
+            }
+            // Maximise the value of `seat`, or minimise it if `sign` is negative
+            let (seat, sign) = match self.backup {
+                Backup::MaxN => (state.turn, 1.0),
+                Backup::Paranoid if state.turn == root => (root, 1.0),
+                Backup::Paranoid => (root, -1.0),
+            };
+            let mut best: Option<Line> = None;
+            let mut complete = true;
+            for action in get_valid_actions(&state.as_view(), &self.set_map) {
+                let undo = state.apply(&action);
+                let line = self.search(state, root, depth - 1);
+                state.undo(undo);
+                if self.nodes > self.max_nodes {
+                    return None;
+                }
+                let Some(mut line) = line else {
+                    complete = false;
+                    continue;
+                };
+                complete &= line.complete;
+                if best
+                    .as_ref()
+                    .is_none_or(|best| sign * line.values[seat] > sign * best.values[seat])
+                {
+                    line.actions.push(action);
+                    best = Some(line);
+                }
+            }
+            let mut best = best?;
+            best.complete = complete;
+            best
+        };
+        if line.complete {
+            let entry = Entry {
+                values: line.values.clone(),
+                best: line.actions.last().copied(),
+            };
+            self.table.insert(key, entry);
+        }
+        Some(line)
+    }
+
+    /// Line of best actions stored in the table from `state`, in reverse order.
+    fn follow(&self, state: &GameState, root: usize) -> Vec<Action> {
+        let mut state = state.clone();
+        let mut actions = Vec::new();
+        while let Some(action) = self
+            .table
+            .get(&self.key(&state, root))
+            .and_then(|entry| entry.best)
+        {
+            state.apply(&action);
+            actions.push(action);
+        }
+        actions.reverse();
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_set_map, Card, Player};
+
+    fn player(hand: &[i32]) -> Player {
+        Player {
+            hand: hand.iter().map(|&value| Card(value, 0)).collect(),
+            captured: 0,
+            tokens: 0,
+            scout_show: 0,
+        }
+    }
+
+    #[test]
+    fn test_solver() {
+        let state = GameState {
+            players: vec![player(&[5, 1]), player(&[2, 7]), player(&[6])].into(),
+            game_size: 3,
+            active: vec![Card(4, 0)].into(),
+            active_owner: 2,
+            turn: 0,
+            turns: 0,
+            round_end: None,
+            rules: Default::default(),
+            captured_cards: Vec::new(),
+            hash: 0,
+        }
+        .with_hash();
+
+        for backup in [Backup::MaxN, Backup::Paranoid] {
+            let mut solver = Solver::new(default_set_map(), backup, 6, 1_000_000);
+            let solution = solver.solve(&state).unwrap();
+            let nodes = solver.nodes();
+
+            // Playing out the principal variation reaches the solved values
+            let mut end = state.clone();
+            for action in &solution.principal_variation {
+                end.apply(action);
+            }
+            assert!(end.round_end.is_some());
+            let values: Vec<f64> = (0..3)
+                .map(|seat| final_value(&end.view_for(seat)).unwrap())
+                .collect();
+            assert_eq!(values, solution.values);
+
+            // Solving again reuses the table
+            assert_eq!(solver.solve(&state), Some(solution));
+            assert!(solver.nodes() < nodes);
+        }
+
+        // With an empty active set, showing a last card is the only action
+        let mut end = state.clone();
+        end.players[0].hand = vec![Card(5, 0)].into();
+        end.active.clear();
+        let mut solver = Solver::new(default_set_map(), Backup::MaxN, 5, 1_000_000);
+        let solution = solver.solve(&end).unwrap();
+        assert!(solution.exact);
+        assert_eq!(solution.principal_variation, vec![Action::Show(0, 0)]);
+
+        // Too large a position gives up
+        let mut solver = Solver::new(default_set_map(), Backup::MaxN, 8, 2);
+        assert_eq!(solver.solve(&state), None);
+    }
+}
@@ -0,0 +1,421 @@
+//! Reinforcement learning environment, in the style of OpenAI Gym.
+//!
+//! A single learning agent plays against `Strategy` opponents. Actions are chosen by index into
+//! an `ActionSpace`, and views are encoded as fixed size vectors of numbers.
+
+use crate::rules::RuleSet;
+use crate::{
+    get_action_isolated, get_valid_actions, Action, GameError, GameState, GameView, Halted, SetMap,
+    Strategy,
+};
+
+/// Maximum number of players, which sets the size of per-player features.
+pub const MAX_PLAYERS: usize = 5;
+
+/// Stable bijection between `Action` values and integer indexes, for hands of up to `max_hand`
+/// cards (including a scouted card). Indexes are laid out as:
+/// - Scout actions, by (left, flip) then insert index
+/// - Show actions, by start then stop
+/// - ScoutShow actions, by (left, flip), insert index, start then stop
+///
+/// Show indexes with `start > stop` don't correspond to an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActionSpace {
+    max_hand: usize,
+}
+
+impl ActionSpace {
+    pub fn new(max_hand: usize) -> ActionSpace {
+        ActionSpace { max_hand }
+    }
+
+    pub fn max_hand(&self) -> usize {
+        self.max_hand
+    }
+
+    /// Total number of indexes.
+    pub fn len(&self) -> usize {
+        let h = self.max_hand;
+        4 * h + h * h + 4 * h * h * h
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_hand == 0
+    }
+
+    /// Index of `action`, or `None` if it refers to a position beyond `max_hand`.
+    pub fn index(&self, action: &Action) -> Option<usize> {
+        let h = self.max_hand;
+        let side = |left: bool, flip: bool| 2 * left as usize + flip as usize;
+        match *action {
+            Action::Scout(left, flip, insert) if insert < h => Some(side(left, flip) * h + insert),
+            Action::Show(start, stop) if start <= stop && stop < h => {
+                Some(4 * h + start * h + stop)
+            }
+            Action::ScoutShow(left, flip, insert, start, stop)
+                if insert < h && start <= stop && stop < h =>
+            {
+                let scout = side(left, flip) * h + insert;
+                Some(4 * h + h * h + (scout * h + start) * h + stop)
+            }
+            _ => None,
+        }
+    }
+
+    /// Action with the given `index`, or `None` if there isn't one.
+    pub fn action(&self, index: usize) -> Option<Action> {
+        let h = self.max_hand;
+        if index < 4 * h {
+            let (left, flip) = (index / h >= 2, (index / h) % 2 == 1);
+            return Some(Action::Scout(left, flip, index % h));
+        }
+        let index = index - 4 * h;
+        if index < h * h {
+            let (start, stop) = (index / h, index % h);
+            return (start <= stop).then_some(Action::Show(start, stop));
+        }
+        let index = index - h * h;
+        if index < 4 * h * h * h {
+            let (scout, start, stop) = (index / (h * h), (index / h) % h, index % h);
+            let (left, flip) = (scout / h >= 2, (scout / h) % 2 == 1);
+            return (start <= stop).then_some(Action::ScoutShow(
+                left,
+                flip,
+                scout % h,
+                start,
+                stop,
+            ));
+        }
+        None
+    }
+
+    /// Legal action mask, with `true` at the index of each action in `actions`.
+    ///
+    /// Panics if an action refers to a position beyond `max_hand`, since the agent could never
+    /// choose it.
+    pub fn mask(&self, actions: &[Action]) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+        for action in actions {
+            let index = self
+                .index(action)
+                .unwrap_or_else(|| panic!("{} is beyond a max hand of {}", action, self.max_hand));
+            mask[index] = true;
+        }
+        mask
+    }
+}
+
+/// Length of the vectors returned by `encode_view`.
+pub fn encoded_len(max_hand: usize) -> usize {
+    4 * max_hand + 4 * MAX_PLAYERS
+}
+
+/// Encode `view` as a fixed size vector. Cards are encoded as their value + 1, with 0 for an
+/// empty slot, and the layout is:
+/// - hand top values, then bottom values (`max_hand` each)
+/// - active set top values, then bottom values (`max_hand` each)
+/// - one-hot active owner, scores, hand sizes and scout show flags (`MAX_PLAYERS` each)
+///
+/// Cards beyond `max_hand` are dropped.
+pub fn encode_view(view: &GameView, max_hand: usize) -> Vec<f32> {
+    let mut features = Vec::with_capacity(encoded_len(max_hand));
+    let mut push_cards = |values: &mut dyn Iterator<Item = i32>| {
+        let mut slots = vec![0.0; max_hand];
+        for (slot, value) in slots.iter_mut().zip(values) {
+            *slot = (value + 1) as f32;
+        }
+        features.extend(slots);
+    };
+    push_cards(&mut view.hand.iter().copied());
+    push_cards(&mut view.hand_bottoms.iter().copied());
+    push_cards(&mut view.active.iter().map(|card| card.top()));
+    push_cards(&mut view.active.iter().map(|card| card.bottom()));
+
+    let mut push_players = |values: &mut dyn Iterator<Item = f32>| {
+        let mut slots = vec![0.0; MAX_PLAYERS];
+        for (slot, value) in slots.iter_mut().zip(values) {
+            *slot = value;
+        }
+        features.extend(slots);
+    };
+    push_players(&mut (0..view.scores.len()).map(|i| (i == view.active_owner) as u8 as f32));
+    push_players(&mut view.scores.iter().map(|&score| score as f32));
+    push_players(&mut view.hand_sizes.iter().map(|&size| size as f32));
+    push_players(&mut view.scout_show.iter().map(|&count| count as f32));
+    features
+}
+
+/// Observation of the game from the agent's seat.
+#[derive(Clone, Debug)]
+pub struct Observation {
+    /// The agent's view, encoded by `encode_view`
+    pub features: Vec<f32>,
+    /// Legal action mask over the `ActionSpace`, all `false` once the game is over
+    pub mask: Vec<bool>,
+}
+
+/// Environment in which an agent in `seat` plays against `opponents` in the other seats.
+///
+/// The reward is 0 until the game ends, then 1 for a win, 0 for a draw and -1 for a loss.
+///
+/// Opponents are isolated as by the runners: an opponent which panics, resigns or plays an
+/// illegal action halts the game with a `GameError`, and the environment must then be reset.
+pub struct Env {
+    seat: usize,
+    opponents: Vec<Box<dyn Strategy>>,
+    space: ActionSpace,
+    set_map: SetMap,
+    rules: RuleSet,
+    game: GameState,
+    legal: Vec<Action>,
+    history: Vec<(usize, Action)>,
+}
+
+impl Env {
+    /// Create an environment, with the number of players set by the number of `opponents`.
+    /// Games are played with the official rules for that many players, or
+    /// `GameError::PlayerCount` is returned if there are none.
+    ///
+    /// Panics if `seat` is out of range, or `max_hand` is less than `RuleSet::max_hand`.
+    pub fn new(
+        opponents: Vec<Box<dyn Strategy>>,
+        seat: usize,
+        max_hand: usize,
+    ) -> Result<Env, GameError> {
+        let n = opponents.len() + 1;
+        let rules = RuleSet::official(n).ok_or(GameError::PlayerCount(n))?;
+        Env::with_rules(opponents, seat, max_hand, rules)
+    }
+
+    /// Create an environment which plays games with the given `rules`. Opponents which rank sets
+    /// should be given `rules.set_map()`.
+    ///
+    /// Returns `GameError::PlayerCount` if the rules can't deal a game for the number of
+    /// players, and panics as `Env::new` does.
+    pub fn with_rules(
+        opponents: Vec<Box<dyn Strategy>>,
+        seat: usize,
+        max_hand: usize,
+        rules: RuleSet,
+    ) -> Result<Env, GameError> {
+        let n = opponents.len() + 1;
+        if !rules.supports(n) {
+            return Err(GameError::PlayerCount(n));
+        }
+        assert!(seat < n, "Seat {} is out of range", seat);
+        assert!(
+            max_hand >= rules.max_hand(n),
+            "A max hand of {} can't encode hands of up to {} cards",
+            max_hand,
+            rules.max_hand(n)
+        );
+        Ok(Env {
+            seat,
+            opponents,
+            space: ActionSpace::new(max_hand),
+            set_map: rules.set_map(),
+            rules,
+            game: GameState::default(),
+            legal: Vec::new(),
+            history: Vec::new(),
+        })
+    }
+
+    pub fn action_space(&self) -> &ActionSpace {
+        &self.space
+    }
+
+    /// The agent's current view of the game.
+    pub fn view(&self) -> GameView {
+        self.game.view_for(self.seat)
+    }
+
+    /// Start a new game, with the deck shuffled from `seed`. Returns the `GameError` if an
+    /// opponent halts the game before the agent's first turn.
+    pub fn reset(&mut self, seed: u64) -> Result<Observation, GameError> {
+        let n = self.opponents.len() + 1;
+        self.game = GameState::with_rules(n, self.rules.clone(), Some(seed))?;
+        self.history.clear();
+        self.play_opponents()?;
+        Ok(self.observe())
+    }
+
+    /// Play the action with index `action_index` for the agent, then the opponents' actions
+    /// until it is the agent's turn again.
+    ///
+    /// Returns `GameError::IllegalAction` for the agent's seat if the action isn't legal (see
+    /// `Observation::mask`), including once the game is over or halted. Returns the opponent's
+    /// `GameError` if an opponent halts the game.
+    ///
+    /// Panics if `action_index` isn't the index of any action in the `ActionSpace`.
+    pub fn step(&mut self, action_index: usize) -> Result<(Observation, f32, bool), GameError> {
+        let action = self
+            .space
+            .action(action_index)
+            .unwrap_or_else(|| panic!("Index {} is not an action", action_index));
+        if !self.legal.contains(&action) {
+            return Err(GameError::IllegalAction(self.halted(self.seat), action));
+        }
+        self.game.apply(&action);
+        self.history.push((self.seat, action));
+        self.play_opponents()?;
+
+        let observation = self.observe();
+        Ok(match self.game.result() {
+            Some(result) => {
+                let reward = match (result.winners.contains(&self.seat), result.winners.len()) {
+                    (true, 1) => 1.0,
+                    (true, _) => 0.0,
+                    (false, _) => -1.0,
+                };
+                (observation, reward, true)
+            }
+            None => (observation, 0.0, false),
+        })
+    }
+
+    /// Details of the game for an error caused by `seat`.
+    fn halted(&self, seat: usize) -> Box<Halted> {
+        Box::new(Halted {
+            seat,
+            view: self.game.view_for(seat),
+            history: self.history.clone(),
+            state: self.game.clone(),
+        })
+    }
+
+    fn play_opponents(&mut self) -> Result<(), GameError> {
+        while self.game.round_end.is_none() && self.game.turn != self.seat {
+            let turn = self.game.turn;
+            let view = self.game.as_view();
+            let opponent = if turn < self.seat { turn } else { turn - 1 };
+            let response = get_action_isolated(self.opponents[opponent].as_mut(), &view);
+            let error = match response {
+                Ok(Some(action)) if get_valid_actions(&view, &self.set_map).contains(&action) => {
+                    self.game.apply(&action);
+                    self.history.push((turn, action));
+                    continue;
+                }
+                Ok(Some(action)) => GameError::IllegalAction(self.halted(turn), action),
+                Ok(None) => GameError::Resigned(self.halted(turn)),
+                Err(kind) => kind.halt(self.halted(turn)),
+            };
+            // The agent can't act in a halted game
+            self.legal.clear();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn observe(&mut self) -> Observation {
+        let view = self.view();
+        self.legal = match self.game.round_end {
+            Some(_) => Vec::new(),
+            None => get_valid_actions(&view, &self.set_map),
+        };
+        Observation {
+            features: encode_view(&view, self.space.max_hand()),
+            mask: self.space.mask(&self.legal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Greedy;
+    use crate::{default_set_map, Card};
+
+    #[test]
+    fn test_action_space() {
+        let space = ActionSpace::new(4);
+        let mut count = 0;
+        for index in 0..space.len() {
+            if let Some(action) = space.action(index) {
+                assert_eq!(space.index(&action), Some(index));
+                count += 1;
+            }
+        }
+        // 4 * 4 scouts, 10 shows, 4 * 4 * 10 scout and shows
+        assert_eq!(count, 16 + 10 + 160);
+        assert_eq!(space.index(&Action::Show(0, 4)), None);
+        assert_eq!(space.action(space.len()), None);
+    }
+
+    #[test]
+    fn test_env() {
+        let opponents: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        let mut env = Env::new(opponents, 1, 34).unwrap();
+
+        // Resetting with a seed deals the same cards
+        let observation = env.reset(7).unwrap();
+        assert_eq!(observation.features, env.reset(7).unwrap().features);
+        assert_eq!(observation.features.len(), encoded_len(34));
+        assert_eq!(observation.mask.len(), env.action_space().len());
+
+        // Play the highest legal index until the game ends
+        let mut observation = observation;
+        loop {
+            let action_index = observation.mask.iter().rposition(|&legal| legal).unwrap();
+            let (next, reward, done) = env.step(action_index).unwrap();
+            if done {
+                assert!([1.0, 0.0, -1.0].contains(&reward));
+                assert!(next.mask.iter().all(|&legal| !legal));
+                break;
+            }
+            assert_eq!(reward, 0.0);
+            observation = next;
+        }
+
+        // Nothing is legal once the game is over
+        assert!(matches!(
+            env.step(0),
+            Err(GameError::IllegalAction(halted, _)) if halted.seat == 1
+        ));
+    }
+
+    /// Panics whenever it is asked for an action
+    struct Panics;
+
+    impl Strategy for Panics {
+        fn get_action(&mut self, _view: &GameView) -> Option<Action> {
+            panic!("no action")
+        }
+    }
+
+    #[test]
+    fn test_env_errors() {
+        let greedy = || Box::new(Greedy(default_set_map())) as Box<dyn Strategy>;
+        assert!(matches!(
+            Env::new((0..5).map(|_| greedy()).collect(), 0, 40),
+            Err(GameError::PlayerCount(6))
+        ));
+
+        // A failing opponent halts the game rather than the training loop
+        let mut env = Env::new(vec![Box::new(Panics), greedy()], 1, 34).unwrap();
+        match env.reset(0) {
+            Err(GameError::StrategyPanic(halted, message)) => {
+                assert_eq!((halted.seat, message.as_str()), (0, "no action"));
+            }
+            _ => panic!("Expected the opponent in seat 0 to panic"),
+        }
+        assert!(matches!(env.step(0), Err(GameError::IllegalAction(..))));
+
+        // Every legal action in the largest hands can be encoded
+        let space = ActionSpace::new(RuleSet::default().max_hand(3));
+        let hand = GameView {
+            hand: vec![0; 33],
+            hand_bottoms: vec![1; 33],
+            active: vec![Card(1, 2)].into(),
+            ..GameState::from_seed(3, 0).unwrap().as_view()
+        };
+        let actions = get_valid_actions(&hand, &default_set_map());
+        assert_eq!(
+            space.mask(&actions).iter().filter(|&&legal| legal).count(),
+            actions.len()
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Heuristics which score positions, shared by greedy and search strategies.
+
+use crate::analysis::{find_sets, HandAnalyser};
+use crate::{get_valid_actions, Action, GameState, GameView, SetMap, Strategy};
+
+/// Value of winning a game outright, which dominates any heuristic score.
+pub const WIN: f64 = 1000.0;
+
+/// Scores a position, where higher is better.
+///
+/// Implementations score a `GameView` for its viewer. A `GameState` is scored for a seat through
+/// that seat's view, unless the implementation can do better with the full state.
+pub trait Evaluator {
+    fn evaluate_view(&mut self, view: &GameView) -> f64;
+
+    fn evaluate(&mut self, state: &GameState, seat: usize) -> f64 {
+        self.evaluate_view(&state.view_for(seat))
+    }
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate_view(&mut self, view: &GameView) -> f64 {
+        (**self).evaluate_view(view)
+    }
+
+    fn evaluate(&mut self, state: &GameState, seat: usize) -> f64 {
+        (**self).evaluate(state, seat)
+    }
+}
+
+/// Value of a finished round for the viewer: `WIN` shared between the winners, or `-WIN` for a
+/// loss, plus the final score margin. Returns `None` if the round hasn't ended.
+pub fn final_value(view: &GameView) -> Option<f64> {
+    let scores = view.final_scores()?;
+    let best = *scores.iter().max().unwrap();
+    let outcome = match scores[0] == best {
+        true => WIN / scores.iter().filter(|&&score| score == best).count() as f64,
+        false => -WIN,
+    };
+    Some(outcome + margin(&scores) as f64)
+}
+
+/// Difference between the first score and the best of the rest.
+fn margin(scores: &[i32]) -> i32 {
+    scores[0] - scores[1..].iter().copied().max().unwrap_or(0)
+}
+
+/// Difference between the viewer's score and the best of the other scores.
+pub struct ScoreMargin;
+
+impl Evaluator for ScoreMargin {
+    fn evaluate_view(&mut self, view: &GameView) -> f64 {
+        margin(&view.scores) as f64
+    }
+}
+
+/// Negated number of shows the viewer needs to empty their hand.
+pub struct TurnsToEmpty {
+    analyser: HandAnalyser,
+}
+
+impl TurnsToEmpty {
+    pub fn new(set_map: SetMap) -> TurnsToEmpty {
+        TurnsToEmpty {
+            analyser: HandAnalyser::new(set_map),
+        }
+    }
+}
+
+impl Evaluator for TurnsToEmpty {
+    fn evaluate_view(&mut self, view: &GameView) -> f64 {
+        -(self.analyser.turns_to_empty(&view.hand) as f64)
+    }
+}
+
+/// Number of distinct sets the viewer could show, ignoring the active set.
+pub struct HandFlexibility {
+    set_map: SetMap,
+}
+
+impl HandFlexibility {
+    pub fn new(set_map: SetMap) -> HandFlexibility {
+        HandFlexibility { set_map }
+    }
+}
+
+impl Evaluator for HandFlexibility {
+    fn evaluate_view(&mut self, view: &GameView) -> f64 {
+        find_sets(&view.hand, &self.set_map).len() as f64
+    }
+}
+
+/// Weighted sum of other evaluators.
+#[derive(Default)]
+pub struct Linear {
+    terms: Vec<(f64, Box<dyn Evaluator>)>,
+}
+
+impl Linear {
+    pub fn new() -> Linear {
+        Linear::default()
+    }
+
+    /// Add `evaluator` with the given `weight`.
+    pub fn term<E: Evaluator + 'static>(mut self, weight: f64, evaluator: E) -> Linear {
+        self.terms.push((weight, Box::new(evaluator)));
+        self
+    }
+}
+
+impl Evaluator for Linear {
+    fn evaluate_view(&mut self, view: &GameView) -> f64 {
+        self.terms
+            .iter_mut()
+            .map(|(weight, evaluator)| *weight * evaluator.evaluate_view(view))
+            .sum()
+    }
+
+    fn evaluate(&mut self, state: &GameState, seat: usize) -> f64 {
+        self.terms
+            .iter_mut()
+            .map(|(weight, evaluator)| *weight * evaluator.evaluate(state, seat))
+            .sum()
+    }
+}
+
+/// Strategy which plays the action leading to the best scored view, looking one action ahead.
+pub struct GreedyStrategy<E> {
+    evaluator: E,
+    set_map: SetMap,
+}
+
+impl<E: Evaluator> GreedyStrategy<E> {
+    pub fn new(evaluator: E, set_map: SetMap) -> Self {
+        GreedyStrategy { evaluator, set_map }
+    }
+}
+
+impl<E: Evaluator> Strategy for GreedyStrategy<E> {
+    fn get_action(&mut self, view: &GameView) -> Option<Action> {
+        let actions = get_valid_actions(view, &self.set_map);
+        let mut view = view.clone();
+        let mut best: Option<(f64, Action)> = None;
+        for action in actions {
+            let undo = view.apply(&action);
+            let value = final_value(&view).unwrap_or_else(|| self.evaluator.evaluate_view(&view));
+            view.undo(undo);
+            // Earlier actions are preferred on ties
+            if best.is_none_or(|(best, _)| value > best) {
+                best = Some((value, action));
+            }
+        }
+        best.map(|(_, action)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_set_map, Card};
+
+    #[test]
+    fn test_evaluators() {
+        let view = GameView {
+            hand: vec![5, 6, 1],
+            hand_bottoms: vec![0, 0, 0],
+            active: vec![Card(3, 4)].into(),
+            active_owner: 2,
+            scores: vec![10, 4, 7],
+            hand_sizes: vec![3, 5, 5],
+            scout_show: vec![0, 1, 1],
+            round_end: None,
+            scoring: Default::default(),
+            captured_cards: Vec::new(),
+            owner_continues: false,
+            hash: 0,
+        }
+        .with_hash();
+        let set_map = default_set_map();
+        assert_eq!(ScoreMargin.evaluate_view(&view), 3.0);
+        assert_eq!(
+            TurnsToEmpty::new(set_map.clone()).evaluate_view(&view),
+            -2.0
+        );
+        // [5], [6], [1] and [5, 6]
+        assert_eq!(
+            HandFlexibility::new(set_map.clone()).evaluate_view(&view),
+            4.0
+        );
+        let mut linear = Linear::new()
+            .term(2.0, ScoreMargin)
+            .term(0.5, TurnsToEmpty::new(set_map.clone()));
+        assert_eq!(linear.evaluate_view(&view), 5.0);
+        assert_eq!(final_value(&view), None);
+
+        // Greedy play shows the pair rather than a single card
+        let mut greedy = GreedyStrategy::new(TurnsToEmpty::new(set_map.clone()), set_map);
+        assert_eq!(greedy.get_action(&view), Some(Action::Show(0, 1)));
+    }
+}
@@ -0,0 +1,211 @@
+//! Seat and deal fairness.
+//!
+//! Play always starts from seat 0, which may give that seat an edge. `seat_reports` measures
+//! win rates by seat over seeded games between identical strategies. Deals can also be scored
+//! for balance by how many shows each hand needs to empty, and `balanced_deal` picks the most
+//! even of several deals for casual games.
+
+use crate::analysis::HandAnalyser;
+use crate::rules::RuleSet;
+use crate::{run_with, top_only, GameError, GameOptions, GameState, Strategy};
+use std::fmt;
+
+/// Win rates by seat for one number of players.
+#[derive(Clone, Debug)]
+pub struct SeatReport {
+    pub players: usize,
+    pub games: usize,
+    /// Fraction of games won from each seat, with shared wins split between the winners
+    pub win_rate: Vec<f64>,
+    /// Standard error of each win rate
+    pub std_error: Vec<f64>,
+    /// Mean final score from each seat
+    pub mean_score: Vec<f64>,
+}
+
+impl fmt::Display for SeatReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} players, {} games:", self.players, self.games)?;
+        for seat in 0..self.players {
+            write!(
+                f,
+                " seat {} {:.1}% ± {:.1} ({:+.2})",
+                seat,
+                100.0 * self.win_rate[seat],
+                100.0 * 1.96 * self.std_error[seat],
+                self.mean_score[seat]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Play `games` seeded games between `strategies`, one per seat, and report how each seat
+/// fared. The strategies should be identical, so that any difference is down to the seat.
+pub fn seat_report(
+    strategies: &mut [Box<dyn Strategy>],
+    games: usize,
+    seed: u64,
+) -> Result<SeatReport, GameError> {
+    let players = strategies.len();
+    let mut wins = vec![0.0; players];
+    let mut scores = vec![0.0; players];
+    for game in 0..games {
+        let options = GameOptions {
+            seed: Some(seed + game as u64),
+            ..Default::default()
+        };
+        let result = run_with(strategies, options)?;
+        for &winner in &result.winners {
+            wins[winner] += 1.0 / result.winners.len() as f64;
+        }
+        for (total, &score) in scores.iter_mut().zip(&result.scores) {
+            *total += score as f64;
+        }
+    }
+
+    let games_played = games.max(1) as f64;
+    let win_rate: Vec<f64> = wins.iter().map(|wins| wins / games_played).collect();
+    Ok(SeatReport {
+        players,
+        games,
+        std_error: win_rate
+            .iter()
+            .map(|rate| (rate * (1.0 - rate) / games_played).sqrt())
+            .collect(),
+        win_rate,
+        mean_score: scores.iter().map(|score| score / games_played).collect(),
+    })
+}
+
+/// Report seat win rates for each number of players in `player_counts`, seating a strategy
+/// built by `factory` in every seat.
+pub fn seat_reports<F: FnMut() -> Box<dyn Strategy>>(
+    mut factory: F,
+    player_counts: &[usize],
+    games: usize,
+    seed: u64,
+) -> Result<Vec<SeatReport>, GameError> {
+    player_counts
+        .iter()
+        .map(|&players| {
+            let mut strategies: Vec<Box<dyn Strategy>> = (0..players).map(|_| factory()).collect();
+            seat_report(&mut strategies, games, seed)
+        })
+        .collect()
+}
+
+/// How evenly the hands of a deal are matched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DealBalance {
+    /// Fewest shows needed to empty each seat's hand, see `HandAnalyser::turns_to_empty`
+    pub turns_to_empty: Vec<usize>,
+}
+
+impl DealBalance {
+    /// Score the hands of `game`, which should be freshly dealt.
+    pub fn new(game: &GameState, analyser: &mut HandAnalyser) -> DealBalance {
+        DealBalance {
+            turns_to_empty: game
+                .players
+                .iter()
+                .map(|player| analyser.turns_to_empty(&top_only(&player.hand)))
+                .collect(),
+        }
+    }
+
+    /// Difference between the best and worst hands, where 0 is perfectly balanced.
+    pub fn spread(&self) -> usize {
+        let max = self.turns_to_empty.iter().max().copied().unwrap_or(0);
+        let min = self.turns_to_empty.iter().min().copied().unwrap_or(0);
+        max - min
+    }
+}
+
+/// Deal a game of `n` players under `rules`, choosing the most balanced of `attempts` deals
+/// shuffled from consecutive seeds starting at `seed`. Stops early on a deal where every hand
+/// needs the same number of shows.
+///
+/// Returns `GameError::PlayerCount` if the rules can't deal a game for `n` players.
+pub fn balanced_deal(
+    n: usize,
+    rules: &RuleSet,
+    attempts: usize,
+    seed: u64,
+) -> Result<(GameState, DealBalance), GameError> {
+    let mut analyser = HandAnalyser::new(rules.set_map());
+    let mut best: Option<(GameState, DealBalance)> = None;
+    for attempt in 0..attempts.max(1) {
+        let game = GameState::with_rules(n, rules.clone(), Some(seed + attempt as u64))?;
+        let balance = DealBalance::new(&game, &mut analyser);
+        if best
+            .as_ref()
+            .is_none_or(|(_, best)| balance.spread() < best.spread())
+        {
+            let even = balance.spread() == 0;
+            best = Some((game, balance));
+            if even {
+                break;
+            }
+        }
+    }
+    Ok(best.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_set_map;
+    use crate::tests::Greedy;
+
+    #[test]
+    fn test_fairness() {
+        let reports = seat_reports(|| Box::new(Greedy(default_set_map())), &[2, 3], 10, 0).unwrap();
+        assert_eq!(reports.len(), 2);
+        for report in &reports {
+            assert_eq!(report.win_rate.len(), report.players);
+            assert!((report.win_rate.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            assert!(report.to_string().contains("seat 0"));
+        }
+        assert!(seat_reports(|| Box::new(Greedy(default_set_map())), &[6], 1, 0).is_err());
+
+        // The balanced deal is at least as even as the first deal tried
+        let mut analyser = HandAnalyser::new(default_set_map());
+        let first = GameState::from_seed(4, 0).unwrap();
+        let (game, balance) = balanced_deal(4, &RuleSet::default(), 10, 0).unwrap();
+        assert!(balance.spread() <= DealBalance::new(&first, &mut analyser).spread());
+        assert_eq!(balance, DealBalance::new(&game, &mut analyser));
+
+        // Balanced deals can be played, but only by the right number of players
+        let mut strategies: Vec<Box<dyn Strategy>> = (0..4)
+            .map(|_| Box::new(Greedy(default_set_map())) as Box<dyn Strategy>)
+            .collect();
+        let options = GameOptions {
+            deal: Some(game.clone()),
+            ..Default::default()
+        };
+        assert!(run_with(&mut strategies, options).is_ok());
+        let options = GameOptions {
+            deal: Some(game),
+            ..Default::default()
+        };
+        assert!(matches!(
+            run_with(&mut strategies[..3], options),
+            Err(GameError::DealSize {
+                dealt: 4,
+                players: 3
+            })
+        ));
+
+        // Variants are dealt under their own rules
+        let rules = RuleSet::mini(5, 4);
+        let (game, balance) = balanced_deal(3, &rules, 10, 0).unwrap();
+        assert!(game.players.iter().all(|player| player.hand.len() == 4));
+        let mut analyser = HandAnalyser::new(rules.set_map());
+        assert_eq!(balance, DealBalance::new(&game, &mut analyser));
+        assert!(matches!(
+            balanced_deal(6, &RuleSet::default(), 1, 0),
+            Err(GameError::PlayerCount(6))
+        ));
+    }
+}
@@ -1,14 +1,56 @@
+pub mod analysis;
+pub mod cfr;
+pub mod dataset;
+pub mod duplicate;
+pub mod endgame;
+pub mod env;
+pub mod evaluation;
+pub mod fairness;
+pub mod rules;
+pub mod search;
+pub mod sprt;
+pub mod stats;
+pub mod tracker;
+pub mod tuning;
+mod zobrist;
+
+use analysis::find_sets;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, SeedableRng};
+use rules::{RuleSet, Scoring};
 use std::collections::{HashMap, VecDeque};
+use std::error::Error;
 use std::fmt;
-
-#[derive(Debug, Clone)]
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// A card, this stores two values, however only the first is "active".
 /// Implements `flip()`, a convenience method which simply flips the two values.
 pub struct Card(i32, i32);
 
 impl Card {
+    /// Create a card with the given `top` (active) and `bottom` values.
+    pub fn new(top: i32, bottom: i32) -> Card {
+        Card(top, bottom)
+    }
+
+    /// The active value of the card.
+    pub fn top(&self) -> i32 {
+        self.0
+    }
+
+    /// The inactive value, which becomes active if the card is flipped.
+    pub fn bottom(&self) -> i32 {
+        self.1
+    }
+
     fn flip(&self) -> Card {
         Card(self.1, self.0)
     }
@@ -17,12 +59,13 @@ impl Card {
 /// A set of cards. This can represent a hand or a set.
 type Set = VecDeque<Card>;
 
-/// Each player has a hand, score, and their "Scout show" move.
-#[derive(Debug, Clone)]
+/// Each player has a hand, captured cards, scout tokens, and their remaining "Scout show" moves.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Player {
     hand: Set,
-    score: i32,
-    scout_show: bool,
+    captured: i32,
+    tokens: i32,
+    scout_show: u32,
 }
 
 /// Player actions. These are Scout, Show and ScoutShow, which each take
@@ -44,13 +87,20 @@ pub enum Action {
 /// This implements game logic to process actions and check scores.
 /// It is also responsible for generating `GameView` objects from the perspective of
 /// the current player (`self.turn`)
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct GameState {
     players: VecDeque<Player>,
     game_size: usize,
     active: Set,
     active_owner: usize,
     turn: usize,
+    turns: usize,
+    round_end: Option<RoundEnd>,
+    rules: RuleSet,
+    /// Cards captured this round, in the order they were captured
+    captured_cards: Vec<Card>,
+    /// Zobrist hash, updated by `apply` and `undo`
+    hash: u64,
 }
 
 /// View from perspective of single player. This is rotated, so vectors such as
@@ -58,25 +108,58 @@ pub struct GameState {
 ///
 /// e.g. a `GameView` from the perspective of player 2 will store player 2's data in
 /// index 0, and player 3's data in index 1.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GameView {
     pub hand: Vec<i32>,
+    /// The inactive value of each card in `hand`
+    pub hand_bottoms: Vec<i32>,
     pub active: Set,
     pub active_owner: usize,
     pub scores: Vec<i32>,
     pub hand_sizes: Vec<usize>,
-    pub scout_show: Vec<bool>,
+    /// Number of scout and show actions each player has left this round
+    pub scout_show: Vec<u32>,
+    pub round_end: Option<RoundEnd>,
+    pub scoring: Scoring,
+    /// Cards captured this round by any player, in the order they were captured. These are
+    /// public, and out of play for the rest of the round.
+    pub captured_cards: Vec<Card>,
+    /// Whether play continues when it returns to the owner of the active set, see
+    /// `RuleSet::owner_continues`
+    pub owner_continues: bool,
+    /// Zobrist hash, updated by `apply` and `undo`
+    hash: u64,
 }
 
-enum NewGameState {
-    Continue(GameState),
-    GameOver(Vec<i32>),
+/// Reason the round ended.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Hash)]
+pub enum RoundEnd {
+    /// A player showed the last cards in their hand
+    HandEmptied,
+    /// Every other player scouted, so play returned to the owner of the active set. Under
+    /// `RuleSet::owner_continues` the owner scouted as well.
+    AllScouted,
+}
+
+/// Record of an action applied in place with `apply`, holding everything required to
+/// restore the previous `GameState` or `GameView` with `undo`.
+#[derive(Debug)]
+pub struct Undo {
+    action: Action,
+    turn: usize,
+    active_owner: usize,
+    scout_show: u32,
+    round_end: Option<RoundEnd>,
+    scouted: Option<Card>,
+    replaced: Option<Set>,
 }
 
-/// Result of taking an action on a GameView. This may end the game, resulting in Win or Loss.
+/// Result of taking an action on a GameView. This may end the game, resulting in Win, Draw
+/// (tied for the highest score) or Loss.
 pub enum NewGameView {
     Continue(GameView),
     Win,
+    Draw,
     Loss,
 }
 
@@ -100,18 +183,13 @@ impl fmt::Display for Action {
     }
 }
 
-impl fmt::Display for Player {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Score: {}, Hand: {:?}", self.score, top_only(&self.hand))
-    }
-}
-
 impl Default for Player {
     fn default() -> Self {
         Player {
             hand: Default::default(),
-            score: Default::default(),
-            scout_show: true,
+            captured: Default::default(),
+            tokens: Default::default(),
+            scout_show: 1,
         }
     }
 }
@@ -120,12 +198,42 @@ impl fmt::Display for GameState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut hands: String = "Hands:\n".to_owned();
         for player in &self.players {
-            hands.push_str(&format!("{}\n", player))
+            hands.push_str(&format!(
+                "Score: {}, Hand: {:?}\n",
+                player.score(&self.rules.scoring),
+                top_only(&player.hand)
+            ))
         }
         write!(f, "{}", hands)
     }
 }
 
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Round ended: {:?} after {} turns",
+            self.round_end, self.turns
+        )?;
+        for (i, player) in self.players.iter().enumerate() {
+            write!(
+                f,
+                "Player {}: {} captured + {} tokens - {} in hand = {}",
+                i,
+                player.captured,
+                player.scout_tokens,
+                player.hand_penalty,
+                player.score()
+            )?;
+            if player.exempt {
+                write!(f, " (exempt)")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "Winners: {:?}", self.winners)
+    }
+}
+
 impl fmt::Display for GameView {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut string: String = Default::default();
@@ -134,11 +242,11 @@ impl fmt::Display for GameView {
         for card in &self.active {
             string.push_str(&format!("{}  |", card.0))
         }
-        string.push_str("\n");
+        string.push('\n');
         for card in &self.active {
             string.push_str(&format!("  {}|", card.1))
         }
-        string.push_str("\n");
+        string.push('\n');
 
         string.push_str(&format!("\nPoints: {} Hand:", self.scores[0]));
         string.push_str(&format!("{:?}", self.hand));
@@ -146,231 +254,556 @@ impl fmt::Display for GameView {
     }
 }
 
+impl Hash for GameState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Hash for GameView {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Player {
+    fn score(&self, scoring: &Scoring) -> i32 {
+        self.captured * scoring.captured as i32 + self.tokens * scoring.tokens as i32
+    }
+}
+
 impl GameState {
-    fn new(n: usize, shuffle: bool) -> Self {
+    /// Create a game of `n` players with the official rules for that many players, or
+    /// `GameError::PlayerCount` if there are none.
+    fn new(n: usize, shuffle: bool) -> Result<Self, GameError> {
+        let rules = RuleSet::official(n).ok_or(GameError::PlayerCount(n))?;
+        let mut deck: Set = rules.deck(n).into();
+        if shuffle {
+            deck.make_contiguous().shuffle(&mut thread_rng());
+        }
+        Ok(Self::deal(n, deck, rules))
+    }
+
+    /// Create a game with the deck shuffled deterministically from `seed`.
+    fn from_seed(n: usize, seed: u64) -> Result<Self, GameError> {
+        let rules = RuleSet::official(n).ok_or(GameError::PlayerCount(n))?;
+        Self::with_rules(n, rules, Some(seed))
+    }
+
+    /// Create a game of `n` players with the given `rules`. The deck is shuffled from `seed`
+    /// if one is given, otherwise at random.
+    ///
+    /// Returns `GameError::PlayerCount` if the rules can't deal a game for `n` players.
+    pub fn with_rules(n: usize, rules: RuleSet, seed: Option<u64>) -> Result<Self, GameError> {
+        if !rules.supports(n) {
+            return Err(GameError::PlayerCount(n));
+        }
+        let mut deck: Set = rules.deck(n).into();
+        match seed {
+            Some(seed) => deck
+                .make_contiguous()
+                .shuffle(&mut StdRng::seed_from_u64(seed)),
+            None => deck.make_contiguous().shuffle(&mut thread_rng()),
+        }
+        Ok(Self::deal(n, deck, rules))
+    }
+
+    fn deal(n: usize, mut deck: Set, rules: RuleSet) -> Self {
+        let player = Player {
+            scout_show: rules.scout_shows,
+            ..Default::default()
+        };
         let mut game = GameState {
             active: Set::new(),
             game_size: n,
-            players: VecDeque::from(vec![Default::default(); n]),
+            players: VecDeque::from(vec![player; n]),
             active_owner: 0,
             turn: 0,
+            turns: 0,
+            round_end: None,
+            rules,
+            captured_cards: Vec::new(),
+            hash: 0,
         };
 
-        let mut deck = create_deck(n, shuffle);
-
-        // Deal out all cards
+        // Deal out all cards, or a hand of the set size to each player
+        if let Some(hand_size) = game.rules.hand_size {
+            deck.truncate(n * hand_size);
+        }
         let mut player_index = 0;
         for card in deck.drain(..) {
             game.players[player_index].hand.push_back(card);
             player_index = (player_index + 1) % n;
         }
-        return game;
+        game.with_hash()
     }
 
-    fn scout(&self, left: bool, flip: bool, index: usize) -> GameState {
-        let mut players = self.players.clone();
-        let game_size = self.game_size;
-        let mut active = self.active.clone();
-        let active_owner = self.active_owner;
+    /// Rules the game is played with.
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// Zobrist hash of the position: every hand, score and scout and show, the active set and
+    /// its owner, the player to move and how the round ended. The number of actions taken isn't
+    /// included, so a position reached in different ways has the same hash.
+    ///
+    /// The hash is updated incrementally by `apply` and `undo`, so is cheap to read.
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    /// Set the hash from scratch, for a newly constructed game.
+    pub(crate) fn with_hash(mut self) -> Self {
+        self.hash =
+            (0..self.game_size).fold(self.shared_hash(), |hash, seat| hash ^ self.seat_hash(seat));
+        self
+    }
+
+    /// Hash of the features which don't belong to a single seat.
+    fn shared_hash(&self) -> u64 {
+        let active = self.active.iter().enumerate();
+        active.fold(
+            zobrist::owner(self.active_owner)
+                ^ zobrist::turn(self.turn)
+                ^ zobrist::round_end(self.round_end),
+            |hash, (i, card)| hash ^ zobrist::card(0, i, card),
+        )
+    }
+
+    /// Hash of the features belonging to `seat`.
+    fn seat_hash(&self, seat: usize) -> u64 {
+        let player = &self.players[seat];
+        let hand = player.hand.iter().enumerate();
+        hand.fold(
+            zobrist::captured(seat, player.captured)
+                ^ zobrist::tokens(seat, player.tokens)
+                ^ zobrist::scout_show(seat, player.scout_show),
+            |hash, (i, card)| hash ^ zobrist::card(seat + 1, i, card),
+        )
+    }
+
+    /// Hash of the features an action by `turn` can change, when `owner` owns the active set.
+    fn action_hash(&self, turn: usize, owner: usize) -> u64 {
+        let hash = self.shared_hash() ^ self.seat_hash(turn);
+        match owner == turn {
+            true => hash,
+            false => hash ^ self.seat_hash(owner),
+        }
+    }
+
+    fn scout(&mut self, left: bool, flip: bool, index: usize) -> Card {
+        let card = if left {
+            self.active.pop_front().unwrap()
+        } else {
+            self.active.pop_back().unwrap()
+        };
         let turn = self.turn;
+        self.players[turn]
+            .hand
+            .insert(index, if flip { card.flip() } else { card.clone() });
+        self.players[self.active_owner].tokens += 1;
+        card
+    }
 
-        let mut card: Card;
+    fn unscout(&mut self, left: bool, index: usize, card: Card, active_owner: usize) {
+        let turn = self.turn;
+        self.players[turn].hand.remove(index);
+        self.players[active_owner].tokens -= 1;
         if left {
-            card = active.pop_front().unwrap();
+            self.active.push_front(card);
         } else {
-            card = active.pop_back().unwrap();
-        }
-        if flip {
-            card = card.flip();
-        }
-        players[turn].hand.insert(index, card);
-        players[active_owner].score += 1;
-
-        GameState {
-            players,
-            game_size,
-            active,
-            active_owner,
-            turn,
+            self.active.push_back(card);
         }
     }
 
-    fn show(&self, start: usize, stop: usize) -> GameState {
-        let mut players = self.players.clone();
-        let game_size = self.game_size;
-        let mut active = self.active.clone();
-        let active_owner = self.turn;
+    fn show(&mut self, start: usize, stop: usize) -> Set {
         let turn = self.turn;
+        let shown: Set = self.players[turn].hand.drain(start..stop + 1).collect();
+        let replaced = std::mem::replace(&mut self.active, shown);
+        self.players[turn].captured += replaced.len() as i32;
+        self.captured_cards.extend(replaced.iter().cloned());
+        self.active_owner = turn;
+        replaced
+    }
 
-        players[turn].score += active.len() as i32;
-        active.clear();
-        for _ in start..stop + 1 {
-            active.push_back(players[turn].hand.remove(start).unwrap())
+    fn unshow(&mut self, start: usize, replaced: Set, active_owner: usize) {
+        let turn = self.turn;
+        self.players[turn].captured -= replaced.len() as i32;
+        let captured = self.captured_cards.len() - replaced.len();
+        self.captured_cards.truncate(captured);
+        let shown = std::mem::replace(&mut self.active, replaced);
+        for (i, card) in shown.into_iter().enumerate() {
+            self.players[turn].hand.insert(start + i, card);
         }
+        self.active_owner = active_owner;
+    }
 
-        GameState {
-            players,
-            game_size,
-            active,
-            active_owner,
-            turn,
-        }
+    /// Apply `action` in place for the current player, returning the `Undo` record which
+    /// restores the previous state when passed to `GameState::undo`.
+    ///
+    /// This does not clone any hands, so is suitable for search.
+    pub fn apply(&mut self, action: &Action) -> Undo {
+        let (turn, owner) = (self.turn, self.active_owner);
+        let before = self.action_hash(turn, owner);
+        let undo = self.apply_action(action);
+        self.hash ^= before ^ self.action_hash(turn, owner);
+        undo
     }
 
-    fn take_action(&self, action: &Action) -> NewGameState {
-        let mut state: GameState;
-        match action {
-            Action::Scout(left, flip, index) => state = self.scout(*left, *flip, *index),
-            Action::Show(start, stop) => state = self.show(*start, *stop),
+    fn apply_action(&mut self, action: &Action) -> Undo {
+        let mut undo = Undo {
+            action: *action,
+            turn: self.turn,
+            active_owner: self.active_owner,
+            scout_show: self.players[self.turn].scout_show,
+            round_end: self.round_end,
+            scouted: None,
+            replaced: None,
+        };
+        match *action {
+            Action::Scout(left, flip, index) => {
+                undo.scouted = Some(self.scout(left, flip, index));
+            }
+            Action::Show(start, stop) => {
+                undo.replaced = Some(self.show(start, stop));
+            }
             Action::ScoutShow(left, flip, index, start, stop) => {
-                state = self.scout(*left, *flip, *index).show(*start, *stop);
-                state.players[self.turn].scout_show = false;
+                undo.scouted = Some(self.scout(left, flip, index));
+                undo.replaced = Some(self.show(start, stop));
+                self.players[self.turn].scout_show -= 1;
             }
         };
+        self.turns += 1;
 
         // Round ends if current players hand is empty
-        if state.players[state.turn].hand.len() == 0 {
-            return NewGameState::GameOver(
-                state
-                    .players
-                    .iter()
-                    .map(|p| p.score - p.hand.len() as i32)
-                    .collect(),
-            );
+        if self.players[self.turn].hand.is_empty() {
+            self.round_end = Some(RoundEnd::HandEmptied);
+            return undo;
         }
+        // The owner scouting from their own set can only happen once everyone else scouted
+        let owner_scouted = matches!(action, Action::Scout(..)) && self.active_owner == self.turn;
         // Progress turn marker
-        state.turn = (state.turn + 1) % state.game_size;
-
-        // Round ends if active_owner is next player
-        if state.active_owner == state.turn {
-            // The next player isn't penalised for their hand size -
-            // offset this players points by their hand size then count normally
-            state.players[state.turn].score += state.players[state.turn].hand.len() as i32;
-            return NewGameState::GameOver(
-                state
-                    .players
-                    .iter()
-                    .map(|p| p.score - p.hand.len() as i32)
-                    .collect(),
-            );
-        } else {
-            return NewGameState::Continue(state);
+        self.turn = (self.turn + 1) % self.game_size;
+
+        // Round ends if active_owner is next player, or if they scouted when play continues
+        if owner_scouted || (!self.rules.owner_continues && self.active_owner == self.turn) {
+            self.round_end = Some(RoundEnd::AllScouted);
+        }
+        undo
+    }
+
+    /// Revert an action previously applied with `GameState::apply`. Undo records must be
+    /// passed back in the reverse order to which they were created.
+    pub fn undo(&mut self, undo: Undo) {
+        let (turn, owner) = (undo.turn, undo.active_owner);
+        let before = self.action_hash(turn, owner);
+        self.undo_action(undo);
+        self.hash ^= before ^ self.action_hash(turn, owner);
+    }
+
+    fn undo_action(&mut self, undo: Undo) {
+        self.round_end = undo.round_end;
+        self.turns -= 1;
+        self.turn = undo.turn;
+        if let Some(replaced) = undo.replaced {
+            match undo.action {
+                Action::Show(start, _) | Action::ScoutShow(_, _, _, start, _) => {
+                    self.unshow(start, replaced, undo.active_owner)
+                }
+                Action::Scout(..) => unreachable!(),
+            }
+        }
+        if let Some(card) = undo.scouted {
+            match undo.action {
+                Action::Scout(left, _, index) | Action::ScoutShow(left, _, index, _, _) => {
+                    self.unscout(left, index, card, undo.active_owner)
+                }
+                Action::Show(..) => unreachable!(),
+            }
         }
+        self.players[self.turn].scout_show = undo.scout_show;
+    }
+
+    /// Breakdown of the final scores if the round has ended, otherwise `None`.
+    /// If every other player scouted, the owner of the active set isn't penalised for
+    /// their hand size.
+    fn result(&self) -> Option<GameResult> {
+        let round_end = self.round_end?;
+        let players: Vec<PlayerResult> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let scoring = &self.rules.scoring;
+                let exempt = scoring.owner_exempt
+                    && round_end == RoundEnd::AllScouted
+                    && i == self.active_owner;
+                PlayerResult {
+                    captured: p.captured * scoring.captured as i32,
+                    scout_tokens: p.tokens * scoring.tokens as i32,
+                    hand_penalty: if exempt { 0 } else { p.hand.len() as i32 },
+                    exempt,
+                }
+            })
+            .collect();
+        let scores: Vec<i32> = players.iter().map(|p| p.score()).collect();
+        let best = *scores.iter().max().unwrap();
+        Some(GameResult {
+            winners: (0..scores.len()).filter(|&i| scores[i] == best).collect(),
+            scores,
+            players,
+            round_end,
+            turns: self.turns,
+            faults: Vec::new(),
+        })
     }
 
     fn as_view(&self) -> GameView {
+        self.view_for(self.turn)
+    }
+
+    /// View of the game from the perspective of `seat`, which need not be the current player.
+    pub fn view_for(&self, seat: usize) -> GameView {
         // Shuffle players left
         // For the view of player 1, player 4 is indexed 3
         let mut players = self.players.clone();
-        players.rotate_left(self.turn);
+        players.rotate_left(seat);
         GameView {
             hand: top_only(&players[0].hand),
+            hand_bottoms: players[0].hand.iter().map(|card| card.1).collect(),
             active: self.active.clone(),
-            active_owner: (self.active_owner + self.game_size - self.turn) % self.game_size,
-            scores: players.iter().map(|p| p.score).collect(),
+            active_owner: (self.active_owner + self.game_size - seat) % self.game_size,
+            scores: players
+                .iter()
+                .map(|p| p.score(&self.rules.scoring))
+                .collect(),
             hand_sizes: players.iter().map(|p| p.hand.len()).collect(),
             scout_show: players.iter().map(|p| p.scout_show).collect(),
+            round_end: self.round_end,
+            scoring: self.rules.scoring,
+            captured_cards: self.captured_cards.clone(),
+            owner_continues: self.rules.owner_continues,
+            hash: 0,
         }
+        .with_hash()
     }
 }
 
 impl GameView {
-    fn scout(&self, left: bool, flip: bool, index: usize) -> GameView {
-        let mut hand = self.hand.clone();
-        let mut active = self.active.clone();
-        let active_owner = self.active_owner;
-        let mut scores = self.scores.clone();
-        let mut hand_sizes = self.hand_sizes.clone();
-        let scout_show = self.scout_show.clone();
-
-        let card: Card;
+    fn scout(&mut self, left: bool, flip: bool, index: usize) -> Card {
+        let card = if left {
+            self.active.pop_front().unwrap()
+        } else {
+            self.active.pop_back().unwrap()
+        };
+        let card_in_hand = if flip { card.flip() } else { card.clone() };
+        self.hand.insert(index, card_in_hand.0);
+        self.hand_bottoms.insert(index, card_in_hand.1);
+        self.hand_sizes[0] += 1;
+        self.scores[self.active_owner] += self.scoring.tokens as i32;
+        card
+    }
+
+    fn unscout(&mut self, left: bool, index: usize, card: Card, active_owner: usize) {
+        self.hand.remove(index);
+        self.hand_bottoms.remove(index);
+        self.hand_sizes[0] -= 1;
+        self.scores[active_owner] -= self.scoring.tokens as i32;
         if left {
-            card = active.pop_front().unwrap();
+            self.active.push_front(card);
         } else {
-            card = active.pop_back().unwrap();
+            self.active.push_back(card);
         }
-        if flip {
-            hand.insert(index, card.1);
-        } else {
-            hand.insert(index, card.0);
+    }
+
+    fn show(&mut self, start: usize, stop: usize) -> Set {
+        let shown: Set = self
+            .hand
+            .drain(start..stop + 1)
+            .zip(self.hand_bottoms.drain(start..stop + 1))
+            .map(|(top, bottom)| Card(top, bottom))
+            .collect();
+        self.hand_sizes[0] -= shown.len();
+        let replaced = std::mem::replace(&mut self.active, shown);
+        self.scores[0] += replaced.len() as i32 * self.scoring.captured as i32;
+        self.captured_cards.extend(replaced.iter().cloned());
+        self.active_owner = 0;
+        replaced
+    }
+
+    fn unshow(&mut self, start: usize, replaced: Set, active_owner: usize) {
+        self.scores[0] -= replaced.len() as i32 * self.scoring.captured as i32;
+        let captured = self.captured_cards.len() - replaced.len();
+        self.captured_cards.truncate(captured);
+        let shown = std::mem::replace(&mut self.active, replaced);
+        self.hand_sizes[0] += shown.len();
+        for (i, card) in shown.into_iter().enumerate() {
+            self.hand.insert(start + i, card.0);
+            self.hand_bottoms.insert(start + i, card.1);
         }
-        hand_sizes[0] += 1;
-        scores[active_owner] += 1;
+        self.active_owner = active_owner;
+    }
 
-        GameView {
-            hand,
-            active,
-            active_owner,
-            scores,
-            hand_sizes,
-            scout_show,
+    /// Apply `action` in place for player 0, returning the `Undo` record which restores
+    /// the previous view when passed to `GameView::undo`.
+    ///
+    /// The view is not rotated afterwards, so player 0 remains the player who acted.
+    pub fn apply(&mut self, action: &Action) -> Undo {
+        let owner = self.active_owner;
+        let before = self.action_hash(owner);
+        let undo = self.apply_action(action);
+        self.hash ^= before ^ self.action_hash(owner);
+        undo
+    }
+
+    fn apply_action(&mut self, action: &Action) -> Undo {
+        let owner_scouted = matches!(action, Action::Scout(..)) && self.active_owner == 0;
+        let mut undo = Undo {
+            action: *action,
+            turn: 0,
+            active_owner: self.active_owner,
+            scout_show: self.scout_show[0],
+            round_end: self.round_end,
+            scouted: None,
+            replaced: None,
+        };
+        match *action {
+            Action::Scout(left, flip, index) => {
+                undo.scouted = Some(self.scout(left, flip, index));
+            }
+            Action::Show(start, stop) => {
+                undo.replaced = Some(self.show(start, stop));
+            }
+            Action::ScoutShow(left, flip, index, start, stop) => {
+                undo.scouted = Some(self.scout(left, flip, index));
+                undo.replaced = Some(self.show(start, stop));
+                self.scout_show[0] -= 1;
+            }
+        };
+
+        // Round ends if current players hand is empty, or active owner is next player (1)
+        // unless play continues, in which case the owner scouting ends it
+        if self.hand.is_empty() {
+            self.round_end = Some(RoundEnd::HandEmptied);
+        } else if owner_scouted || (!self.owner_continues && self.active_owner == 1) {
+            self.round_end = Some(RoundEnd::AllScouted);
         }
+        undo
     }
 
-    fn show(&self, start: usize, stop: usize) -> GameView {
-        let mut hand = self.hand.clone();
-        let mut active = self.active.clone();
-        let active_owner = 0;
-        let mut scores = self.scores.clone();
-        let hand_sizes = self.hand_sizes.clone();
-        let scout_show = self.scout_show.clone();
+    /// Revert an action previously applied with `GameView::apply`. Undo records must be
+    /// passed back in the reverse order to which they were created.
+    pub fn undo(&mut self, undo: Undo) {
+        let owner = undo.active_owner;
+        let before = self.action_hash(owner);
+        self.undo_action(undo);
+        self.hash ^= before ^ self.action_hash(owner);
+    }
 
-        scores[0] += active.len() as i32;
-        active.clear();
-        for _ in start..stop + 1 {
-            active.push_back(Card(hand.remove(start), 0))
+    fn undo_action(&mut self, undo: Undo) {
+        self.round_end = undo.round_end;
+        if let Some(replaced) = undo.replaced {
+            match undo.action {
+                Action::Show(start, _) | Action::ScoutShow(_, _, _, start, _) => {
+                    self.unshow(start, replaced, undo.active_owner)
+                }
+                Action::Scout(..) => unreachable!(),
+            }
+        }
+        if let Some(card) = undo.scouted {
+            match undo.action {
+                Action::Scout(left, _, index) | Action::ScoutShow(left, _, index, _, _) => {
+                    self.unscout(left, index, card, undo.active_owner)
+                }
+                Action::Show(..) => unreachable!(),
+            }
         }
+        self.scout_show[0] = undo.scout_show;
+    }
 
-        GameView {
-            hand,
-            active,
-            active_owner,
-            scores,
-            hand_sizes,
-            scout_show,
+    /// Final scores if the round has ended, otherwise `None`.
+    /// If every other player scouted, the owner of the active set may be exempt from the
+    /// penalty for their hand size.
+    pub fn final_scores(&self) -> Option<Vec<i32>> {
+        let round_end = self.round_end?;
+        let exempt = self.scoring.owner_exempt && round_end == RoundEnd::AllScouted;
+        Some(
+            (0..self.scores.len())
+                .map(|i| match exempt && i == self.active_owner {
+                    true => self.scores[i],
+                    false => self.scores[i] - self.hand_sizes[i] as i32,
+                })
+                .collect(),
+        )
+    }
+
+    /// Zobrist hash of the view, from the same keys as `GameState::zobrist` with the viewer as
+    /// seat 0. Other players' hands are only hashed by size, and their scores as totals.
+    ///
+    /// The hash is updated incrementally by `apply` and `undo`. After changing fields of a
+    /// view directly, call `with_hash` to recompute it.
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    /// Set the hash from scratch, for a view whose fields were changed directly.
+    pub fn with_hash(mut self) -> Self {
+        self.hash = (0..self.scores.len())
+            .fold(self.shared_hash(), |hash, seat| hash ^ self.seat_hash(seat));
+        self
+    }
+
+    /// Hash of the features which don't belong to a single seat.
+    fn shared_hash(&self) -> u64 {
+        let active = self.active.iter().enumerate();
+        active.fold(
+            zobrist::owner(self.active_owner) ^ zobrist::round_end(self.round_end),
+            |hash, (i, card)| hash ^ zobrist::card(0, i, card),
+        )
+    }
+
+    /// Hash of the features belonging to `seat`. Only the viewer's hand is known.
+    fn seat_hash(&self, seat: usize) -> u64 {
+        let hash = zobrist::score(seat, self.scores[seat])
+            ^ zobrist::hand_size(seat, self.hand_sizes[seat])
+            ^ zobrist::scout_show(seat, self.scout_show[seat]);
+        if seat > 0 {
+            return hash;
         }
+        let hand = self.hand.iter().zip(&self.hand_bottoms).enumerate();
+        hand.fold(hash, |hash, (i, (&top, &bottom))| {
+            hash ^ zobrist::card(1, i, &Card(top, bottom))
+        })
     }
 
-    pub fn take_action(&self, action: &Action) -> NewGameView {
-        let mut view: GameView;
-        match action {
-            Action::Scout(left, flip, index) => view = self.scout(*left, *flip, *index),
-            Action::Show(start, stop) => view = self.show(*start, *stop),
-            Action::ScoutShow(left, flip, index, start, stop) => {
-                view = self.scout(*left, *flip, *index).show(*start, *stop);
-                view.scout_show[0] = false;
-            }
-        };
+    /// Hash of the features an action by player 0 can change, when `owner` owns the
+    /// active set.
+    fn action_hash(&self, owner: usize) -> u64 {
+        let hash = self.shared_hash() ^ self.seat_hash(0);
+        match owner == 0 {
+            true => hash,
+            false => hash ^ self.seat_hash(owner),
+        }
+    }
 
-        // Round ends if current players hand is empty
-        if view.hand.len() == 0 {
-            let mut final_scores = Vec::new();
-            for i in 0..view.scores.len() {
-                final_scores.push(view.scores[i] - view.hand_sizes[i] as i32);
-            }
-            if final_scores[0] == *final_scores.iter().max().unwrap() {
-                return NewGameView::Win;
-            } else {
-                return NewGameView::Loss;
-            };
-        // Round ends if active owner is next player (1)
-        } else if view.active_owner == 1 {
-            // The next player isn't penalised for their hand size -
-            // offset this players points by their hand size then count normally
-            view.scores[1] += view.hand.len() as i32;
-            let mut final_scores = Vec::new();
-            for i in 0..view.scores.len() {
-                final_scores.push(view.scores[i] - view.hand_sizes[i] as i32);
-            }
-            if final_scores[0] == *final_scores.iter().max().unwrap() {
-                return NewGameView::Win;
-            } else {
-                return NewGameView::Loss;
-            };
+    /// Outcome of the view for player 0, as returned by `take_action`.
+    pub fn outcome(&self) -> Option<NewGameView> {
+        let final_scores = self.final_scores()?;
+        let best = *final_scores.iter().max().unwrap();
+        if final_scores[0] < best {
+            Some(NewGameView::Loss)
+        } else if final_scores.iter().filter(|&&s| s == best).count() > 1 {
+            Some(NewGameView::Draw)
         } else {
-            return NewGameView::Continue(view);
+            Some(NewGameView::Win)
         }
     }
+
+    pub fn take_action(&self, action: &Action) -> NewGameView {
+        let mut view = self.clone();
+        view.apply(action);
+        view.outcome().unwrap_or(NewGameView::Continue(view))
+    }
 }
 
 /// Strategy trait implements get_action method to generates an `Action` based on a `GameView`.
@@ -380,52 +813,410 @@ pub trait Strategy {
     fn get_action(&mut self, view: &GameView) -> Option<Action>;
 }
 
+/// Observes the games played by a runner, e.g. to collect statistics. Every method does nothing
+/// by default.
+pub trait Observer {
+    /// Called when a game has been dealt, before any action.
+    fn on_start(&mut self, _game: &GameState) {}
+
+    /// Called for each action, with the game before `seat` plays `action`.
+    fn on_action(&mut self, _game: &GameState, _seat: usize, _action: &Action) {}
+
+    /// Called when a game completes. Halted games have no result.
+    fn on_result(&mut self, _result: &GameResult) {}
+}
+
+/// Observer which ignores every game.
+impl Observer for () {}
+
+/// Final score breakdown for a single player.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct PlayerResult {
+    /// Cards captured by showing over other sets
+    pub captured: i32,
+    /// Tokens received when other players scouted from this player's set
+    pub scout_tokens: i32,
+    /// Points lost for cards remaining in hand
+    pub hand_penalty: i32,
+    /// Whether this player owned the active set when the round ended by every other
+    /// player scouting, and so wasn't penalised for their hand
+    pub exempt: bool,
+}
+
+impl PlayerResult {
+    pub fn score(&self) -> i32 {
+        self.captured + self.scout_tokens - self.hand_penalty
+    }
+}
+
+/// Result of a completed game. Vectors are indexed by seat.
+#[derive(Clone, Debug)]
 pub struct GameResult {
     pub scores: Vec<i32>,
+    pub players: Vec<PlayerResult>,
+    pub round_end: RoundEnd,
+    /// Number of actions taken during the game
+    pub turns: usize,
+    /// Seats tied for the highest score
+    pub winners: Vec<usize>,
+    /// Strategy failures which were replaced with a fallback action
+    pub faults: Vec<Fault>,
 }
 
-/// Run a single game of Scout. The length of `strategies` determines the number of players, and the
-/// `Strategy` function each player uses.
-///
-/// Returns `GameResult` object containing final scores,
-/// or in the case of runtime error, the `GameState` which lead to the error.
-pub fn run(strategies: &mut Vec<Box<dyn Strategy>>) -> Result<GameResult, GameState> {
-    let n_players = strategies.len();
-    let mut game = GameState::new(n_players, true);
+/// Ways a strategy may fail to produce an action.
+#[derive(Clone, Debug)]
+pub enum FaultKind {
+    /// The strategy exceeded its time budget
+    Timeout(Duration),
+    /// The strategy panicked, with the panic message
+    Panic(String),
+}
 
-    loop {
-        let action = strategies[game.turn].get_action(&game.as_view());
-        match action {
-            Some(action) => {
-                match game.take_action(&action) {
-                    NewGameState::Continue(new) => game = new,
-                    NewGameState::GameOver(scores) => {
-                        return Ok(GameResult { scores });
-                    }
-                };
+impl FaultKind {
+    /// The error halting a game because of this fault.
+    fn halt(self, halted: Box<Halted>) -> GameError {
+        match self {
+            Self::Timeout(budget) => GameError::Timeout(halted, budget),
+            Self::Panic(message) => GameError::StrategyPanic(halted, message),
+        }
+    }
+}
+
+/// A strategy failure, recorded in `GameResult` when play continued with a fallback action.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    pub seat: usize,
+    /// Number of actions taken before the failure
+    pub turn: usize,
+    /// The view which was passed to the failing strategy
+    pub view: GameView,
+    pub kind: FaultKind,
+}
+
+/// How the runner handles a strategy which fails to produce an action.
+pub enum Failover {
+    /// Halt the game with a `GameError`, forfeiting the seat
+    Forfeit,
+    /// Play the action chosen by this strategy instead, and record the `Fault`
+    Fallback(Box<dyn Strategy>),
+}
+
+/// Time limits for `run_timed`.
+#[derive(Clone, Debug)]
+pub struct TimeControl {
+    /// Budget for each call to `Strategy::get_action`
+    pub per_move: Duration,
+    /// Optional total time for all of a player's calls, chess clock style
+    pub bank: Option<Duration>,
+}
+
+/// A strategy which can be called from a worker thread, see `run_timed`.
+pub type SharedStrategy = Arc<Mutex<dyn Strategy + Send>>;
+
+/// Details of a game which was halted before completion.
+#[derive(Debug)]
+pub struct Halted {
+    /// Seat of the player responsible for halting the game
+    pub seat: usize,
+    /// The view which was passed to the offending strategy
+    pub view: GameView,
+    /// Every action taken before the game halted, with the seat which took it
+    pub history: Vec<(usize, Action)>,
+    pub state: GameState,
+}
+
+/// Reasons a game may be halted before completion.
+#[derive(Debug)]
+pub enum GameError {
+    /// The strategy returned `None`, e.g. a human player quitting
+    Resigned(Box<Halted>),
+    /// The strategy returned an action which isn't valid for its view
+    IllegalAction(Box<Halted>, Action),
+    /// The strategy panicked, with the panic message
+    StrategyPanic(Box<Halted>, String),
+    /// The strategy exceeded its time budget
+    Timeout(Box<Halted>, Duration),
+    /// The game can't be dealt for this number of players, so never started
+    PlayerCount(usize),
+    /// A game dealt for `dealt` players was given to `players` strategies, so never started
+    DealSize { dealt: usize, players: usize },
+}
+
+impl GameError {
+    /// Details of the halted game, common to all errors from a game which started.
+    pub fn halted(&self) -> Option<&Halted> {
+        match self {
+            Self::Resigned(halted)
+            | Self::IllegalAction(halted, _)
+            | Self::StrategyPanic(halted, _)
+            | Self::Timeout(halted, _) => Some(halted),
+            Self::PlayerCount(_) | Self::DealSize { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let halted = match self {
+            Self::PlayerCount(n) => return write!(f, "Scout can't be played with {} players", n),
+            Self::DealSize { dealt, players } => {
+                return write!(
+                    f,
+                    "A game dealt for {} players can't be played by {}",
+                    dealt, players
+                )
             }
-            None => {
-                return Err(game);
+            _ => self.halted().unwrap(),
+        };
+        match self {
+            Self::Resigned(_) => write!(f, "Player {} resigned", halted.seat),
+            Self::IllegalAction(_, action) => {
+                write!(
+                    f,
+                    "Player {} played an illegal action: {}",
+                    halted.seat, action
+                )
+            }
+            Self::StrategyPanic(_, message) => {
+                write!(f, "Player {} strategy panicked: {}", halted.seat, message)
             }
+            Self::Timeout(_, budget) => {
+                write!(
+                    f,
+                    "Player {} exceeded time budget of {:?}",
+                    halted.seat, budget
+                )
+            }
+            Self::PlayerCount(_) | Self::DealSize { .. } => unreachable!(),
+        }?;
+        write!(f, " after {} actions", halted.history.len())
+    }
+}
+
+impl Error for GameError {}
+
+/// Options for a game played by `run_with` or `run_timed`. The default deals a game at random
+/// under the official rules for the number of players, which is played silently and forfeited
+/// by any strategy which panics.
+pub struct GameOptions<'a> {
+    /// Rules to deal and play under, or `None` for the official rules for the number of players.
+    /// Strategies which rank sets themselves should be given `rules.set_map()`.
+    pub rules: Option<RuleSet>,
+    /// Seed to shuffle the deck from, or `None` to shuffle at random. Games with the same seed,
+    /// rules and number of players are dealt the same hands, which allows strategies to be
+    /// compared fairly.
+    pub seed: Option<u64>,
+    /// An already dealt game to play, such as one from `fairness::balanced_deal`. This is
+    /// played in place of dealing a game from `rules` and `seed`.
+    pub deal: Option<GameState>,
+    /// Observer to report the game to
+    pub observer: Option<&'a mut dyn Observer>,
+    /// How to handle strategies which panic or time out. A strategy which panics is not called
+    /// again for that move, so with `Failover::Fallback` the game continues and each panic is
+    /// recorded in `GameResult::faults` with its triggering view.
+    pub failover: Failover,
+    /// Whether to print the game as it is played, as `watch` does, and if so whether to show
+    /// every player's hand
+    pub show_hands: Option<bool>,
+}
+
+impl Default for GameOptions<'_> {
+    fn default() -> Self {
+        GameOptions {
+            rules: None,
+            seed: None,
+            deal: None,
+            observer: None,
+            failover: Failover::Forfeit,
+            show_hands: None,
+        }
+    }
+}
+
+impl GameOptions<'_> {
+    /// The game to play with `n` players, taking the deal if there is one.
+    fn game(&mut self, n: usize) -> Result<GameState, GameError> {
+        match (self.deal.take(), self.rules.take(), self.seed) {
+            (Some(game), ..) if game.game_size != n => Err(GameError::DealSize {
+                dealt: game.game_size,
+                players: n,
+            }),
+            (Some(game), ..) => Ok(game),
+            (None, Some(rules), seed) => GameState::with_rules(n, rules, seed),
+            (None, None, Some(seed)) => GameState::from_seed(n, seed),
+            (None, None, None) => GameState::new(n, true),
         }
     }
 }
 
+/// Run a single game of Scout. The length of `strategies` determines the number of players, and the
+/// `Strategy` function each player uses. Two players play the official two-player variant.
+///
+/// Returns `GameResult` object containing the final score breakdown,
+/// or a `GameError` describing why the game was halted.
+pub fn run(strategies: &mut [Box<dyn Strategy>]) -> Result<GameResult, GameError> {
+    run_with(strategies, GameOptions::default())
+}
+
 /// Watch a single game of Scout. The length of `strategies` determines the number of players, and the
 /// `Strategy` function each player uses.
 ///
-/// Returns `GameResult` object containing final scores,
-/// or in the case of runtime error, the `GameState` which lead to the error.
+/// Returns `GameResult` object containing the final score breakdown,
+/// or a `GameError` describing why the game was halted.
 pub fn watch(
-    strategies: &mut Vec<Box<dyn Strategy>>,
+    strategies: &mut [Box<dyn Strategy>],
     show_hands: bool,
-) -> Result<GameResult, GameState> {
-    let n_players = strategies.len();
-    let mut game = GameState::new(n_players, true);
+) -> Result<GameResult, GameError> {
+    let options = GameOptions {
+        show_hands: Some(show_hands),
+        ..Default::default()
+    };
+    run_with(strategies, options)
+}
+
+/// Run a single game of Scout as set out by `options`, such as under other rules, from a seed or
+/// an existing deal, or reported to an observer.
+///
+/// Returns `GameError::PlayerCount` if the game can't be dealt for the number of `strategies`,
+/// or `GameError::DealSize` if `options.deal` was dealt for a different number.
+pub fn run_with(
+    strategies: &mut [Box<dyn Strategy>],
+    mut options: GameOptions,
+) -> Result<GameResult, GameError> {
+    let game = options.game(strategies.len())?;
+    let mut unobserved = ();
+    let observer: &mut dyn Observer = match options.observer {
+        Some(observer) => observer,
+        None => &mut unobserved,
+    };
+    play(
+        game,
+        options.show_hands,
+        &mut options.failover,
+        observer,
+        |seat, view| get_action_isolated(strategies[seat].as_mut(), view),
+    )
+}
+
+/// Run a single game of Scout as set out by `options`, with each call to `Strategy::get_action`
+/// limited by `time_control`.
+///
+/// Strategies are called on worker threads. A strategy which times out is left to finish on its
+/// thread, and its late action is discarded - it will keep its lock until then, so later calls
+/// spend their budget waiting for it. Timeouts and panics are handled according to
+/// `options.failover`.
+pub fn run_timed(
+    strategies: &[SharedStrategy],
+    time_control: &TimeControl,
+    mut options: GameOptions,
+) -> Result<GameResult, GameError> {
+    let mut banks = vec![time_control.bank; strategies.len()];
+    let game = options.game(strategies.len())?;
+    let mut unobserved = ();
+    let observer: &mut dyn Observer = match options.observer {
+        Some(observer) => observer,
+        None => &mut unobserved,
+    };
+    play(
+        game,
+        options.show_hands,
+        &mut options.failover,
+        observer,
+        |seat, view| {
+            let budget = match banks[seat] {
+                Some(bank) => time_control.per_move.min(bank),
+                None => time_control.per_move,
+            };
+            let start = Instant::now();
+            let response = get_action_within(&strategies[seat], view, budget);
+            if let Some(bank) = banks[seat].as_mut() {
+                *bank = bank.saturating_sub(start.elapsed());
+            }
+            response
+        },
+    )
+}
+
+/// Call `strategy` on a new thread, waiting up to `budget` for its action. If the call times
+/// out before the thread gets the strategy, for example because an earlier call still holds it,
+/// the strategy isn't called with the stale view.
+fn get_action_within(
+    strategy: &SharedStrategy,
+    view: &GameView,
+    budget: Duration,
+) -> Result<Option<Action>, FaultKind> {
+    if budget.is_zero() {
+        return Err(FaultKind::Timeout(budget));
+    }
+    let strategy = Arc::clone(strategy);
+    let view = view.clone();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn({
+        let cancelled = Arc::clone(&cancelled);
+        move || {
+            // A previous panic shouldn't prevent the strategy being used again
+            let mut strategy = strategy
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            // The receiver is gone if this call timed out
+            let _ = sender.send(get_action_isolated(&mut *strategy, &view));
+        }
+    });
+    match receiver.recv_timeout(budget) {
+        Ok(response) => response,
+        Err(RecvTimeoutError::Timeout) => {
+            cancelled.store(true, Ordering::SeqCst);
+            Err(FaultKind::Timeout(budget))
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(FaultKind::Panic("strategy thread panicked".to_owned()))
+        }
+    }
+}
+
+/// Call `strategy`, catching any panic as a `FaultKind::Panic`.
+fn get_action_isolated(
+    strategy: &mut dyn Strategy,
+    view: &GameView,
+) -> Result<Option<Action>, FaultKind> {
+    panic::catch_unwind(AssertUnwindSafe(|| strategy.get_action(view))).map_err(|payload| {
+        // Panic payloads are a `&str` or `String` unless raised with `panic_any`
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => "unknown panic".to_owned(),
+            },
+        };
+        FaultKind::Panic(message)
+    })
+}
+
+/// Game loop shared by the runners, playing out `game`. `get_action` requests an action for a
+/// seat, failures are handled according to `failover`, and `observer` sees every action.
+/// Progress is printed if `show_hands` is given.
+fn play<F>(
+    mut game: GameState,
+    show_hands: Option<bool>,
+    failover: &mut Failover,
+    observer: &mut dyn Observer,
+    mut get_action: F,
+) -> Result<GameResult, GameError>
+where
+    F: FnMut(usize, &GameView) -> Result<Option<Action>, FaultKind>,
+{
+    observer.on_start(&game);
+    let set_map = game.rules.set_map();
+    let mut history = Vec::new();
+    let mut faults = Vec::new();
     let mut round = 0;
 
     loop {
-        if game.turn == 0 {
+        if let (0, Some(show_hands)) = (game.turn, show_hands) {
             println!("\nRound {}", round);
             if show_hands {
                 println!("{}", game)
@@ -434,59 +1225,58 @@ pub fn watch(
         }
 
         // Get action using strategy
-        let action = strategies[game.turn].get_action(&game.as_view());
+        let seat = game.turn;
+        let view = game.as_view();
+        let halted = |view, history, state| {
+            Box::new(Halted {
+                seat,
+                view,
+                history,
+                state,
+            })
+        };
+        let response = match (get_action(seat, &view), &mut *failover) {
+            (Err(kind), Failover::Fallback(fallback)) => {
+                faults.push(Fault {
+                    seat,
+                    turn: history.len(),
+                    view: view.clone(),
+                    kind,
+                });
+                get_action_isolated(fallback.as_mut(), &view)
+            }
+            (response, _) => response,
+        };
+        let action = match response {
+            Ok(action) => action,
+            Err(kind) => return Err(kind.halt(halted(view, history, game))),
+        };
         match action {
             Some(action) => {
-                println!("Active: {:?}", top_only(&game.active));
-                println!("Player {} plays: {}", &game.turn, action);
-                match game.take_action(&action) {
-                    NewGameState::Continue(new) => game = new,
-                    NewGameState::GameOver(scores) => {
-                        return Ok(GameResult { scores });
-                    }
-                };
-            }
-            None => {
-                return Err(game);
-            }
-        }
-    }
-}
-
-fn create_deck(game_size: usize, shuffle: bool) -> Set {
-    let mut deck: Set = VecDeque::new();
-    match game_size {
-        3 => {
-            // Each unique combination of 0-8, excluding matches
-            for bottom in 0..9 {
-                for top in 0..bottom {
-                    deck.push_back(Card(top, bottom));
+                if !get_valid_actions(&view, &set_map).contains(&action) {
+                    return Err(GameError::IllegalAction(
+                        halted(view, history, game),
+                        action,
+                    ));
                 }
-            }
-        }
-        4 => {
-            // Each unique combination of 0-9, excluding matches and (9/8)
-            for bottom in 0..10 {
-                for top in 0..bottom {
-                    deck.push_back(Card(top, bottom));
+                if show_hands.is_some() {
+                    println!("Active: {:?}", top_only(&game.active));
+                    println!("Player {} plays: {}", seat, action);
                 }
-            }
-            deck.pop_back();
-        }
-        5 => {
-            // Each unique combination of 0-9, excluding matches
-            for bottom in 0..10 {
-                for top in 0..bottom {
-                    deck.push_back(Card(top, bottom));
+                observer.on_action(&game, seat, &action);
+                game.apply(&action);
+                history.push((seat, action));
+                if let Some(mut result) = game.result() {
+                    result.faults = faults;
+                    observer.on_result(&result);
+                    return Ok(result);
                 }
             }
+            None => {
+                return Err(GameError::Resigned(halted(view, history, game)));
+            }
         }
-        _ => {}
     }
-    if shuffle {
-        deck.make_contiguous().shuffle(&mut thread_rng());
-    }
-    return deck;
 }
 
 /// To efficiently compare the value of sets, this hashmap is created.
@@ -494,34 +1284,7 @@ pub type SetMap = HashMap<Vec<i32>, i32>;
 
 /// Generate the default set hierarchy for the deck of 0-9 valued cards
 pub fn default_set_map() -> SetMap {
-    // Generate all legal sets, and assign an i32 value to each.
-    // This is done by generating the sets in order of their value
-
-    let mut map = HashMap::new();
-    let mut i = 1;
-
-    // For size=1 straights and flushes are identical
-    for base in 0..10 {
-        map.insert(vec![base], i);
-        i = i + 1;
-    }
-
-    // Iterate up to max Set size
-    for size in 2..10 {
-        // First add all straights (ascending and descending)
-        for base in 0..10 {
-            map.insert((base..base + size).collect(), i);
-            map.insert((base..base + size).rev().collect(), i);
-            i = i + 1;
-        }
-        // Then add all flushes (this is done after to preserve order)
-        for base in 0..10 {
-            map.insert(vec![base; size as usize], i);
-            i = i + 1;
-        }
-    }
-
-    return map;
+    RuleSet::default().set_map()
 }
 
 fn top_only(set: &Set) -> Vec<i32> {
@@ -550,18 +1313,12 @@ pub fn get_valid_actions(view: &GameView, set_map: &SetMap) -> Vec<Action> {
     // Show actions
     let active_set_score = set_map.get(&top_only(&view.active)).unwrap_or(&0);
     let hand = &view.hand;
-    for start in 0..hand.len() {
-        for stop in start..hand.len() {
-            if let Some(score) = set_map.get(&hand[start..stop + 1]) {
-                if *score > *active_set_score {
-                    actions.push(Action::Show(start, stop))
-                }
-            }
-        }
+    for set in find_sets(hand, set_map).beating(*active_set_score) {
+        actions.push(Action::Show(set.start, set.stop))
     }
 
     // Scout and show actions
-    if !view.scout_show[0] | view.active.is_empty() {
+    if view.scout_show[0] == 0 || view.active.is_empty() {
         return actions;
     }
 
@@ -588,20 +1345,14 @@ pub fn get_valid_actions(view: &GameView, set_map: &SetMap) -> Vec<Action> {
                 }
 
                 let new_active_set_score = set_map.get(&top_only(&new_active)).unwrap_or(&0);
-                for start in 0..new_hand.len() {
-                    for stop in start..new_hand.len() {
-                        if let Some(score) = set_map.get(&new_hand[start..stop + 1]) {
-                            if *score > *new_active_set_score {
-                                actions.push(Action::ScoutShow(left, flip, i, start, stop))
-                            }
-                        }
-                    }
+                for set in find_sets(&new_hand, set_map).beating(*new_active_set_score) {
+                    actions.push(Action::ScoutShow(left, flip, i, set.start, set.stop))
                 }
             }
         }
     }
 
-    return actions;
+    actions
 }
 
 #[cfg(test)]
@@ -612,14 +1363,60 @@ mod tests {
 
     #[test]
     fn test_game_init() {
-        let game = GameState::new(3, false);
+        let game = GameState::new(3, false).unwrap();
         assert_eq!(game.players[0].hand.len(), 12);
-        let game = GameState::new(4, false);
+        let game = GameState::new(4, false).unwrap();
         assert_eq!(game.players[0].hand.len(), 11);
-        let game = GameState::new(5, false);
+        let game = GameState::new(5, false).unwrap();
         assert_eq!(game.players[0].hand.len(), 9);
     }
 
+    #[test]
+    fn test_two_player() {
+        let game = GameState::new(2, true).unwrap();
+        assert!(game.players.iter().all(|p| p.hand.len() == 11));
+        assert_eq!(game.as_view().scout_show, vec![3, 3]);
+
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        let result = run(&mut strategies).unwrap();
+        assert!(result.players.iter().all(|p| !p.exempt));
+
+        // A scout hands play back to the owner of the active set without ending the round
+        let mut game = GameState::from_seed(2, 0).unwrap();
+        let pair = get_valid_actions(&game.as_view(), &default_set_map())
+            .into_iter()
+            .find(|action| matches!(action, Action::Show(start, stop) if stop > start))
+            .unwrap();
+        game.apply(&pair);
+        let mut view = game.as_view();
+        game.apply(&Action::Scout(true, false, 0));
+        view.apply(&Action::Scout(true, false, 0));
+        assert_eq!((game.round_end, game.turn), (None, 0));
+        assert_eq!(view.round_end, None);
+
+        // The owner may show again, but the round ends once they scout too
+        let mut view = game.as_view();
+        assert!(get_valid_actions(&view, &default_set_map())
+            .iter()
+            .any(|action| matches!(action, Action::Show(..))));
+        let undo = game.apply(&Action::Scout(true, false, 0));
+        view.apply(&Action::Scout(true, false, 0));
+        assert_eq!(game.round_end, Some(RoundEnd::AllScouted));
+        assert_eq!(view.round_end, Some(RoundEnd::AllScouted));
+        game.undo(undo);
+        assert_eq!(game.round_end, None);
+
+        // Unsupported player counts aren't dealt
+        assert!(matches!(
+            run(&mut strategies[..1]),
+            Err(GameError::PlayerCount(1))
+        ));
+        assert!(GameState::new(6, true).is_err());
+    }
+
     #[test]
     fn test_set_map() {
         let set_map = default_set_map();
@@ -628,7 +1425,7 @@ mod tests {
         assert_eq!(set_map.get(&Vec::new()), None);
 
         // Minimum set must score 1 (0 is empty set score)
-        assert_eq!(set_map.get(&vec![0 as i32]), Some(&1));
+        assert_eq!(set_map.get(&vec![0]), Some(&1));
 
         // Larger sets beat smaller sets
         assert!(set_map.get(&vec![1, 1, 1]).unwrap() > set_map.get(&vec![9, 9]).unwrap());
@@ -646,11 +1443,17 @@ mod tests {
         let set_map = default_set_map();
         let base_view = GameView {
             hand: Vec::new(),
+            hand_bottoms: Vec::new(),
             active: VecDeque::new(),
             active_owner: 3,
             hand_sizes: vec![1, 1, 1, 1],
             scores: vec![0, 0, 0, 0],
-            scout_show: vec![false, false, false, false],
+            scout_show: vec![0, 0, 0, 0],
+            round_end: None,
+            scoring: Scoring::default(),
+            captured_cards: Vec::new(),
+            owner_continues: false,
+            hash: 0,
         };
 
         // Test basic show cases
@@ -689,7 +1492,7 @@ mod tests {
         view.hand.push(0); // hand: [0]
         view.active.push_back(Card(3, 0)); // this 0 can be used with scoutshow
         view.active.push_back(Card(3, 3)); // active: [3, 3]
-        view.scout_show[0] = true;
+        view.scout_show[0] = 1;
         let actions: HashSet<Action> = get_valid_actions(&view, &set_map).iter().copied().collect();
         assert_eq!(
             actions,
@@ -707,4 +1510,352 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_apply_undo() {
+        let set_map = default_set_map();
+        let mut game = GameState::new(4, true).unwrap();
+
+        // Every valid action must be exactly reversible, for both the state and its view
+        while game.round_end.is_none() {
+            let view = game.as_view();
+            let actions = get_valid_actions(&view, &set_map);
+            let before = format!("{:?}", game);
+            let view_before = format!("{:?}", view);
+            for action in &actions {
+                let undo = game.apply(action);
+                game.undo(undo);
+                assert_eq!(format!("{:?}", game), before);
+
+                let mut new_view = view.clone();
+                let undo = new_view.apply(action);
+                new_view.undo(undo);
+                assert_eq!(format!("{:?}", new_view), view_before);
+            }
+
+            // Applying to the view matches the view of the applied state
+            let action = actions[actions.len() / 2];
+            let turn = game.turn;
+            let mut new_view = view.clone();
+            new_view.apply(&action);
+            game.apply(&action);
+            assert_eq!(new_view.hand, top_only(&game.players[turn].hand));
+            let bottoms: Vec<i32> = game.players[turn].hand.iter().map(|c| c.1).collect();
+            assert_eq!(new_view.hand_bottoms, bottoms);
+            assert_eq!(
+                new_view.scores[0],
+                game.players[turn].score(&game.rules.scoring)
+            );
+            assert_eq!(new_view.round_end, game.round_end);
+        }
+    }
+
+    #[test]
+    fn test_zobrist() {
+        let set_map = default_set_map();
+        let mut game = GameState::from_seed(3, 1).unwrap();
+        let mut seen = HashSet::new();
+
+        while game.round_end.is_none() {
+            let hash = game.zobrist();
+            let view_hash = game.as_view().zobrist();
+            assert!(seen.insert(hash));
+            for action in get_valid_actions(&game.as_view(), &set_map) {
+                // The incremental hash matches one computed from scratch, and undo restores it
+                let undo = game.apply(&action);
+                assert_eq!(game.zobrist(), game.clone().with_hash().zobrist());
+                assert_ne!(game.zobrist(), hash);
+                game.undo(undo);
+                assert_eq!(game.zobrist(), hash);
+
+                // The same holds for views, including actions applied in sequence
+                let mut view = game.as_view();
+                let undo = view.apply(&action);
+                assert_eq!(view.zobrist(), view.clone().with_hash().zobrist());
+                assert_ne!(view.zobrist(), view_hash);
+                let after = view.zobrist();
+                if view.round_end.is_none() {
+                    for action in get_valid_actions(&view, &set_map) {
+                        let undo = view.apply(&action);
+                        assert_eq!(view.zobrist(), view.clone().with_hash().zobrist());
+                        view.undo(undo);
+                        assert_eq!(view.zobrist(), after);
+                    }
+                }
+                view.undo(undo);
+                assert_eq!(view.zobrist(), view_hash);
+            }
+            let action = Greedy(set_map.clone()).get_action(&game.as_view()).unwrap();
+            game.apply(&action);
+        }
+
+        // Dealing the same cards gives the same hash, and equal states hash equally
+        let dealt = GameState::from_seed(3, 1).unwrap();
+        assert!(seen.contains(&dealt.zobrist()));
+        assert_ne!(
+            dealt.zobrist(),
+            GameState::from_seed(3, 2).unwrap().zobrist()
+        );
+        assert!(HashSet::from([dealt.clone()]).contains(&dealt));
+    }
+
+    #[test]
+    fn test_game_result() {
+        let set_map = default_set_map();
+        let mut game = GameState::new(3, true).unwrap();
+        let mut turns = 0;
+
+        // Always play the largest available set, otherwise scout
+        while game.result().is_none() {
+            let actions = get_valid_actions(&game.as_view(), &set_map);
+            let action = actions
+                .iter()
+                .filter(|a| matches!(a, Action::Show(..)))
+                .max_by_key(|a| match a {
+                    Action::Show(start, stop) => stop - start,
+                    _ => 0,
+                })
+                .unwrap_or(&actions[0]);
+            game.apply(action);
+            turns += 1;
+        }
+
+        let result = game.result().unwrap();
+        assert_eq!(result.turns, turns);
+        for (score, player) in result.scores.iter().zip(&result.players) {
+            assert_eq!(*score, player.score());
+        }
+        let best = *result.scores.iter().max().unwrap();
+        assert!(result.winners.iter().all(|&i| result.scores[i] == best));
+        match result.round_end {
+            RoundEnd::HandEmptied => {
+                assert!(result.players.iter().all(|p| !p.exempt));
+                assert_eq!(result.players[game.turn].hand_penalty, 0);
+            }
+            RoundEnd::AllScouted => {
+                assert!(result.players[game.active_owner].exempt);
+                assert_eq!(result.players.iter().filter(|p| p.exempt).count(), 1);
+            }
+        }
+    }
+
+    /// Plays `action` if given, otherwise resigns
+    pub(crate) struct Fixed(pub(crate) Option<Action>);
+
+    impl Strategy for Fixed {
+        fn get_action(&mut self, _view: &GameView) -> Option<Action> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_run_errors() {
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Fixed(Some(Action::Show(0, 0)))),
+            Box::new(Fixed(None)),
+            Box::new(Fixed(None)),
+        ];
+        match run(&mut strategies) {
+            Err(GameError::Resigned(halted)) => {
+                assert_eq!(halted.seat, 1);
+                assert_eq!(halted.history, vec![(0, Action::Show(0, 0))]);
+                assert_eq!(halted.view.hand.len(), 12);
+            }
+            _ => panic!("Expected player 1 to resign"),
+        }
+
+        strategies[1] = Box::new(Fixed(Some(Action::Show(20, 20))));
+        match run(&mut strategies) {
+            Err(err @ GameError::IllegalAction(..)) => {
+                assert_eq!(err.halted().unwrap().seat, 1);
+                assert_eq!(
+                    err.to_string(),
+                    "Player 1 played an illegal action: Show 20 to 20 after 1 actions"
+                );
+            }
+            _ => panic!("Expected player 1 to play an illegal action"),
+        }
+    }
+
+    /// Plays the longest valid show, otherwise the first valid action
+    pub(crate) struct Greedy(pub(crate) SetMap);
+
+    impl Strategy for Greedy {
+        fn get_action(&mut self, view: &GameView) -> Option<Action> {
+            let actions = get_valid_actions(view, &self.0);
+            actions
+                .iter()
+                .filter_map(|action| match action {
+                    Action::Show(start, stop) => Some((stop - start, action)),
+                    _ => None,
+                })
+                .max_by_key(|(len, _)| *len)
+                .map(|(_, action)| action)
+                .or(actions.first())
+                .copied()
+        }
+    }
+
+    /// Resigns, but only after the given delay, counting how many times it was called
+    struct Slow(Duration, usize);
+
+    impl Strategy for Slow {
+        fn get_action(&mut self, _view: &GameView) -> Option<Action> {
+            self.1 += 1;
+            thread::sleep(self.0);
+            None
+        }
+    }
+
+    #[test]
+    fn test_run_timed() {
+        let strategies: Vec<SharedStrategy> = vec![
+            Arc::new(Mutex::new(Slow(Duration::from_millis(500), 0))),
+            Arc::new(Mutex::new(Greedy(default_set_map()))),
+            Arc::new(Mutex::new(Greedy(default_set_map()))),
+        ];
+        let mut time_control = TimeControl {
+            per_move: Duration::from_millis(50),
+            bank: None,
+        };
+
+        // The slow strategy forfeits
+        match run_timed(&strategies, &time_control, GameOptions::default()) {
+            Err(GameError::Timeout(halted, budget)) => {
+                assert_eq!(halted.seat, 0);
+                assert_eq!(budget, Duration::from_millis(50));
+            }
+            _ => panic!("Expected player 0 to time out"),
+        }
+
+        // Or is replaced by the fallback on every turn
+        let options = GameOptions {
+            failover: Failover::Fallback(Box::new(Greedy(default_set_map()))),
+            ..Default::default()
+        };
+        let result = run_timed(&strategies, &time_control, options).unwrap();
+        assert!(!result.faults.is_empty());
+        assert!(result.faults.iter().all(|fault| fault.seat == 0));
+        assert!(result
+            .faults
+            .iter()
+            .all(|fault| matches!(fault.kind, FaultKind::Timeout(_))));
+
+        // An empty bank times out immediately, even with a generous move budget
+        time_control.per_move = Duration::from_secs(10);
+        time_control.bank = Some(Duration::ZERO);
+        assert!(matches!(
+            run_timed(&strategies[1..], &time_control, GameOptions::default()),
+            Err(GameError::Timeout(..))
+        ));
+
+        // A call which times out while waiting for the strategy never reaches it
+        let slow = Arc::new(Mutex::new(Slow(Duration::from_millis(200), 0)));
+        let shared: SharedStrategy = slow.clone();
+        let view = GameState::from_seed(3, 0).unwrap().as_view();
+        for _ in 0..2 {
+            assert!(matches!(
+                get_action_within(&shared, &view, Duration::from_millis(20)),
+                Err(FaultKind::Timeout(_))
+            ));
+        }
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(slow.lock().unwrap().1, 1);
+    }
+
+    /// Panics on the first action by indexing past the end of the hand
+    struct Panicky;
+
+    impl Strategy for Panicky {
+        fn get_action(&mut self, view: &GameView) -> Option<Action> {
+            Some(Action::Show(view.hand[20] as usize, 0))
+        }
+    }
+
+    #[test]
+    fn test_run_with_failover() {
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Panicky),
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+
+        match run(&mut strategies) {
+            Err(GameError::StrategyPanic(halted, message)) => {
+                assert_eq!(halted.seat, 0);
+                assert!(halted.history.is_empty());
+                assert!(message.contains("index out of bounds"));
+            }
+            _ => panic!("Expected player 0 to panic"),
+        }
+
+        let options = GameOptions {
+            failover: Failover::Fallback(Box::new(Greedy(default_set_map()))),
+            ..Default::default()
+        };
+        let result = run_with(&mut strategies, options).unwrap();
+        let fault = &result.faults[0];
+        assert_eq!((fault.seat, fault.turn), (0, 0));
+        assert_eq!(fault.view.hand.len(), 12);
+        assert!(matches!(&fault.kind, FaultKind::Panic(message) if message.contains("index")));
+    }
+
+    #[test]
+    fn test_run_with() {
+        let rules = RuleSet {
+            deck: rules::Deck::AllPairs,
+            values: (0, 5),
+            scoring: Scoring {
+                tokens: false,
+                ..Default::default()
+            },
+            scout_shows: 2,
+            ..Default::default()
+        };
+        let game = GameState::with_rules(3, rules.clone(), Some(0)).unwrap();
+        assert_eq!(game.players[0].hand.len(), 5);
+        assert_eq!(game.as_view().scout_show, vec![2, 2, 2]);
+
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(rules.set_map())),
+            Box::new(Greedy(rules.set_map())),
+            Box::new(Greedy(rules.set_map())),
+        ];
+        let options = || GameOptions {
+            rules: Some(rules.clone()),
+            seed: Some(0),
+            ..Default::default()
+        };
+        let result = run_with(&mut strategies, options()).unwrap();
+        assert!(result.players.iter().all(|p| p.scout_tokens == 0));
+        let replay = run_with(&mut strategies, options()).unwrap();
+        assert_eq!(replay.scores, result.scores);
+
+        // The same game can be played from its deal
+        let options = GameOptions {
+            deal: Some(GameState::with_rules(3, rules.clone(), Some(0)).unwrap()),
+            ..Default::default()
+        };
+        let dealt = run_with(&mut strategies, options).unwrap();
+        assert_eq!(dealt.scores, result.scores);
+
+        // Seeded games with the official rules are the games played without any rules
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        let official = GameOptions {
+            rules: RuleSet::official(2),
+            seed: Some(3),
+            ..Default::default()
+        };
+        let seeded = GameOptions {
+            seed: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_with(&mut strategies, official).unwrap().scores,
+            run_with(&mut strategies, seeded).unwrap().scores
+        );
+    }
 }
@@ -13,10 +13,14 @@ fn main() {
 
     match scout_game::watch(&mut strategies, false) {
         Ok(game_result) => {
-            println!("Game over! Scores: {:?}", game_result.scores);
+            println!("Game over!\n{}", game_result);
         }
-        Err(game) => {
-            println!("Game halted!: {:?}", game);
+        Err(err) => {
+            println!(
+                "Game halted: {}\n{:?}",
+                err,
+                err.halted().map(|halted| &halted.state)
+            );
             process::exit(1);
         }
     }
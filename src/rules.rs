@@ -0,0 +1,256 @@
+//! Rule variants. `RuleSet::default()` gives the official rules.
+
+use crate::{Card, SetMap};
+use std::collections::HashMap;
+
+/// Which cards make up the deck.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Deck {
+    /// The published decks, which depend on the number of players: every pair of values
+    /// 0-8 for 3 players, 0-9 without the 9/8 card for 2 or 4 players, and 0-9 for 5 players
+    Official,
+    /// Every pair of distinct values in the value range
+    AllPairs,
+    /// A fixed list of cards, as (top, bottom) values
+    Custom(Vec<(i32, i32)>),
+}
+
+/// How sets of the same length are ranked, before comparing their values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOrdering {
+    /// Flushes (matching values) beat straights
+    FlushesHigher,
+    /// Straights beat flushes
+    StraightsHigher,
+}
+
+/// How final scores are counted. Every player loses a point for each card left in hand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Scoring {
+    /// Whether each captured card scores a point
+    pub captured: bool,
+    /// Whether each scout token scores a point
+    pub tokens: bool,
+    /// Whether the owner of the active set avoids the hand penalty when the round ends by
+    /// every other player scouting
+    pub owner_exempt: bool,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            captured: true,
+            tokens: true,
+            owner_exempt: true,
+        }
+    }
+}
+
+/// Rules of a game, carried by `GameState`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleSet {
+    pub deck: Deck,
+    /// Lowest and highest card values, which set the range of the set ranking
+    pub values: (i32, i32),
+    pub ordering: SetOrdering,
+    pub scoring: Scoring,
+    /// Number of scout and show actions each player may take per round
+    pub scout_shows: u32,
+    /// Number of cards dealt to each player, with the rest of the deck left out of the round.
+    /// The whole deck is dealt if `None`.
+    pub hand_size: Option<usize>,
+    /// Whether play continues when every other player has scouted and play returns to the
+    /// owner of the active set. The owner may then show over their own set, and the round only
+    /// ends if they scout as well.
+    pub owner_continues: bool,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            deck: Deck::Official,
+            values: (0, 9),
+            ordering: SetOrdering::FlushesHigher,
+            scoring: Scoring::default(),
+            scout_shows: 1,
+            hand_size: None,
+            owner_continues: false,
+        }
+    }
+}
+
+impl RuleSet {
+    /// The official two-player variant. Each player is dealt 11 cards from the four-player
+    /// deck and may scout and show three times. A scout hands play back to the owner of the
+    /// active set without ending the round, which only ends once both players scout in turn,
+    /// and nobody is exempt from the hand penalty.
+    pub fn two_player() -> RuleSet {
+        RuleSet {
+            scoring: Scoring {
+                owner_exempt: false,
+                ..Default::default()
+            },
+            scout_shows: 3,
+            hand_size: Some(11),
+            owner_continues: true,
+            ..Default::default()
+        }
+    }
+
+    /// A smaller game for research, with every pair of values from 0 to `max_value` in the
+    /// deck and `hand_size` cards dealt to each player. Sets are ranked for the smaller range
+    /// by `set_map`, and the other rules are official.
+    pub fn mini(max_value: i32, hand_size: usize) -> RuleSet {
+        RuleSet {
+            deck: Deck::AllPairs,
+            values: (0, max_value),
+            hand_size: Some(hand_size),
+            ..Default::default()
+        }
+    }
+
+    /// Official rules for `game_size` players, or `None` if the game isn't published for that
+    /// many players.
+    pub fn official(game_size: usize) -> Option<RuleSet> {
+        match game_size {
+            2 => Some(RuleSet::two_player()),
+            3..=5 => Some(RuleSet::default()),
+            _ => None,
+        }
+    }
+
+    /// Whether a game of `game_size` players can be dealt: there must be at least two players,
+    /// and enough cards for each to be dealt a hand.
+    pub fn supports(&self, game_size: usize) -> bool {
+        game_size >= 2 && self.deck(game_size).len() >= game_size * self.hand_size.unwrap_or(1)
+    }
+
+    /// Most cards a player can hold just after scouting in a game of `game_size` players: every
+    /// dealt card, except the one each other player must keep for the round to continue.
+    pub fn max_hand(&self, game_size: usize) -> usize {
+        let deck = self.deck(game_size).len();
+        let dealt = match self.hand_size {
+            Some(hand_size) => deck.min(game_size * hand_size),
+            None => deck,
+        };
+        (dealt + 1).saturating_sub(game_size)
+    }
+
+    /// Unshuffled deck for a game of `game_size` players, with the lower value of each card on
+    /// top. Official decks are empty for unsupported numbers of players.
+    pub fn deck(&self, game_size: usize) -> Vec<Card> {
+        let pairs = |low: i32, high: i32| {
+            (low..=high).flat_map(move |bottom| (low..bottom).map(move |top| Card(top, bottom)))
+        };
+        match &self.deck {
+            Deck::Official => match game_size {
+                // Each unique combination of 0-8, excluding matches
+                3 => pairs(0, 8).collect(),
+                // Each unique combination of 0-9, excluding matches and (9/8)
+                2 | 4 => pairs(0, 9).filter(|card| *card != Card(8, 9)).collect(),
+                // Each unique combination of 0-9, excluding matches
+                5 => pairs(0, 9).collect(),
+                _ => Vec::new(),
+            },
+            Deck::AllPairs => pairs(self.values.0, self.values.1).collect(),
+            Deck::Custom(cards) => cards
+                .iter()
+                .map(|&(top, bottom)| Card(top, bottom))
+                .collect(),
+        }
+    }
+
+    /// Ranking of every legal set for this value range and ordering. Longer sets always win, and
+    /// sets of the same length and kind are ranked by value.
+    pub fn set_map(&self) -> SetMap {
+        // Generate all legal sets, and assign an i32 value to each.
+        // This is done by generating the sets in order of their value
+        let (low, high) = self.values;
+        let mut map = HashMap::new();
+        let mut i = 1;
+
+        // For size=1 straights and flushes are identical
+        for base in low..=high {
+            map.insert(vec![base], i);
+            i += 1;
+        }
+
+        // Iterate up to max Set size, adding each kind of set in order, with the lower ranked
+        // kind first
+        let kinds = match self.ordering {
+            SetOrdering::FlushesHigher => [false, true],
+            SetOrdering::StraightsHigher => [true, false],
+        };
+        for size in 2..=high - low + 1 {
+            for flush in kinds {
+                for base in low..=high {
+                    if flush {
+                        map.insert(vec![base; size as usize], i);
+                    } else if base + size - 1 <= high {
+                        // Ascending and descending straights are equal
+                        map.insert((base..base + size).collect(), i);
+                        map.insert((base..base + size).rev().collect(), i);
+                    } else {
+                        continue;
+                    }
+                    i += 1;
+                }
+            }
+        }
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_set_map;
+
+    #[test]
+    fn test_rule_set() {
+        let rules = RuleSet::default();
+        assert_eq!(rules.deck(3).len(), 36);
+        assert_eq!(rules.deck(4).len(), 44);
+        assert_eq!(rules.deck(5).len(), 45);
+        assert!(!rules.supports(6));
+        assert_eq!(rules.max_hand(3), 34);
+
+        // Two players are dealt part of the four-player deck
+        let rules = RuleSet::official(2).unwrap();
+        assert_eq!(rules.deck(2).len(), 44);
+        assert!(rules.supports(2));
+        assert!(RuleSet::official(1).is_none());
+        assert_eq!(rules.set_map()[&vec![9, 9]], default_set_map()[&vec![9, 9]]);
+
+        // Straights can be ranked above flushes
+        let rules = RuleSet {
+            ordering: SetOrdering::StraightsHigher,
+            ..Default::default()
+        };
+        let set_map = rules.set_map();
+        assert!(set_map[&vec![1, 2]] > set_map[&vec![9, 9]]);
+        assert!(set_map[&vec![1, 1, 1]] > set_map[&vec![9, 8]]);
+
+        // Smaller value ranges have smaller decks and rankings
+        let rules = RuleSet {
+            deck: Deck::AllPairs,
+            values: (0, 4),
+            ..Default::default()
+        };
+        assert_eq!(rules.deck(3).len(), 10);
+        assert!(!rules.set_map().contains_key(&vec![5]));
+
+        let rules = RuleSet::mini(4, 3);
+        assert_eq!(rules.deck(2), RuleSet::mini(4, 5).deck(3));
+        assert!(rules.supports(3));
+        assert!(!rules.supports(4));
+        assert_eq!(rules.set_map().len(), RuleSet::mini(4, 5).set_map().len());
+
+        // Straights span the whole value range, and never leave it
+        let set_map = RuleSet::mini(4, 5).set_map();
+        assert!(set_map[&vec![0, 1, 2, 3, 4]] > set_map[&vec![4, 4, 4, 4]]);
+        assert_eq!(set_map[&vec![4, 3, 2, 1, 0]], set_map[&vec![0, 1, 2, 3, 4]]);
+        assert!(!set_map.contains_key(&vec![4, 5]));
+    }
+}
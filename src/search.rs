@@ -0,0 +1,308 @@
+//! Depth limited search over determinized games.
+//!
+//! Hidden hands are sampled with `CardTracker::determinize`, from the cards which are neither
+//! in the searching player's hand, the active set nor already captured. Each sample is then
+//! searched a few plies deep with paranoid or max-n backup. Positions at the depth limit are scored by an
+//! `Evaluator`.
+
+use crate::evaluation::{final_value, Evaluator};
+use crate::rules::RuleSet;
+use crate::tracker::CardTracker;
+use crate::{get_valid_actions, Action, GameState, GameView, SetMap, Strategy};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Value of a finished game for `seat`.
+fn terminal(state: &GameState, seat: usize) -> f64 {
+    final_value(&state.view_for(seat)).unwrap()
+}
+
+/// How values are backed up the search tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backup {
+    /// Assume every opponent plays to minimise the searching player's value, which allows
+    /// alpha-beta pruning
+    Paranoid,
+    /// Assume every player maximises their own value
+    MaxN,
+}
+
+/// Options for `SearchStrategy`.
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    /// Number of plies searched, including the searching player's action
+    pub depth: usize,
+    /// Number of sampled deals of the hidden hands
+    pub determinizations: usize,
+    /// Number of actions searched below the root, taken in move order
+    pub width: Option<usize>,
+    pub backup: Backup,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            depth: 2,
+            determinizations: 8,
+            width: Some(8),
+            backup: Backup::Paranoid,
+        }
+    }
+}
+
+/// Strategy which plays the action with the best total value over several determinizations.
+/// Cards captured earlier in the round are listed in the view, so are never dealt to opponents.
+pub struct SearchStrategy<E> {
+    config: SearchConfig,
+    evaluator: E,
+    set_map: SetMap,
+    rules: Option<RuleSet>,
+    rng: StdRng,
+}
+
+impl<E: Evaluator> SearchStrategy<E> {
+    pub fn new(evaluator: E, set_map: SetMap, config: SearchConfig, seed: u64) -> Self {
+        SearchStrategy {
+            config,
+            evaluator,
+            set_map,
+            rules: None,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Create a strategy for games with the given `rules`, rather than the official rules for
+    /// the number of players. Hidden hands are sampled from the deck for these rules.
+    pub fn with_rules(evaluator: E, rules: RuleSet, config: SearchConfig, seed: u64) -> Self {
+        SearchStrategy {
+            set_map: rules.set_map(),
+            rules: Some(rules),
+            ..SearchStrategy::new(evaluator, SetMap::new(), config, seed)
+        }
+    }
+
+    /// Legal actions from the current player's view, longest shows first.
+    fn ordered_actions(&self, view: &GameView) -> Vec<Action> {
+        let mut actions = get_valid_actions(view, &self.set_map);
+        actions.sort_by_key(|action| match *action {
+            Action::Show(start, stop) => (usize::MAX - (stop - start), 0),
+            Action::ScoutShow(.., start, stop) => (usize::MAX - (stop - start), 1),
+            Action::Scout(..) => (usize::MAX, 2),
+        });
+        actions
+    }
+
+    /// Actions searched from a position below the root.
+    fn children(&self, state: &GameState) -> Vec<Action> {
+        let mut actions = self.ordered_actions(&state.as_view());
+        if let Some(width) = self.config.width {
+            actions.truncate(width);
+        }
+        actions
+    }
+
+    /// Value for seat 0, assuming the other seats minimise it.
+    fn paranoid(
+        &mut self,
+        state: &mut GameState,
+        depth: usize,
+        mut alpha: f64,
+        mut beta: f64,
+    ) -> f64 {
+        if state.round_end.is_some() {
+            return terminal(state, 0);
+        }
+        if depth == 0 {
+            return self.evaluator.evaluate(state, 0);
+        }
+        let maximising = state.turn == 0;
+        let mut best = if maximising {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        };
+        for action in self.children(state) {
+            let undo = state.apply(&action);
+            let value = self.paranoid(state, depth - 1, alpha, beta);
+            state.undo(undo);
+            if maximising {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Values for every seat, assuming each seat maximises their own.
+    fn max_n(&mut self, state: &mut GameState, depth: usize) -> Vec<f64> {
+        let seats = 0..state.game_size;
+        if state.round_end.is_some() {
+            return seats.map(|seat| terminal(state, seat)).collect();
+        }
+        if depth == 0 {
+            return seats
+                .map(|seat| self.evaluator.evaluate(state, seat))
+                .collect();
+        }
+        let turn = state.turn;
+        let mut best: Option<Vec<f64>> = None;
+        for action in self.children(state) {
+            let undo = state.apply(&action);
+            let values = self.max_n(state, depth - 1);
+            state.undo(undo);
+            if best.as_ref().is_none_or(|best| values[turn] > best[turn]) {
+                best = Some(values);
+            }
+        }
+        best.unwrap_or_else(|| vec![0.0; state.game_size])
+    }
+}
+
+impl<E: Evaluator> Strategy for SearchStrategy<E> {
+    fn get_action(&mut self, view: &GameView) -> Option<Action> {
+        let actions = self.ordered_actions(view);
+        if actions.len() <= 1 {
+            return actions.first().copied();
+        }
+
+        let tracker = match &self.rules {
+            Some(rules) => CardTracker::with_rules(view.hand_sizes.len(), rules),
+            // Without official rules the deck is unknown, so there is nothing to search
+            None => match CardTracker::new(view.hand_sizes.len()) {
+                Ok(tracker) => tracker,
+                Err(_) => return actions.first().copied(),
+            },
+        };
+        let depth = self.config.depth.max(1) - 1;
+        let mut totals = vec![0.0; actions.len()];
+        for _ in 0..self.config.determinizations.max(1) {
+            let mut state = tracker.determinize(view, &mut self.rng);
+            for (total, action) in totals.iter_mut().zip(&actions) {
+                let undo = state.apply(action);
+                *total += match self.config.backup {
+                    Backup::Paranoid => {
+                        self.paranoid(&mut state, depth, f64::NEG_INFINITY, f64::INFINITY)
+                    }
+                    Backup::MaxN => self.max_n(&mut state, depth)[0],
+                };
+                state.undo(undo);
+            }
+        }
+
+        // Earlier actions are preferred on ties
+        let best = (0..actions.len()).fold(0, |best, i| match totals[i] > totals[best] {
+            true => i,
+            false => best,
+        });
+        Some(actions[best])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::{ScoreMargin, TurnsToEmpty};
+    use crate::tests::Greedy;
+    use crate::{default_set_map, run_with, Card, GameOptions};
+
+    #[test]
+    fn test_search() {
+        // Showing both cards empties the hand and wins the game
+        let view = GameView {
+            hand: vec![5, 6],
+            hand_bottoms: vec![0, 0],
+            active: vec![Card(3, 4)].into(),
+            active_owner: 1,
+            scores: vec![10, 0, 0],
+            hand_sizes: vec![2, 5, 5],
+            scout_show: vec![0, 1, 1],
+            round_end: None,
+            scoring: Default::default(),
+            captured_cards: Vec::new(),
+            owner_continues: false,
+            hash: 0,
+        }
+        .with_hash();
+        for backup in [Backup::Paranoid, Backup::MaxN] {
+            let config = SearchConfig {
+                backup,
+                ..Default::default()
+            };
+            let mut search = SearchStrategy::new(ScoreMargin, default_set_map(), config, 0);
+            assert_eq!(search.get_action(&view), Some(Action::Show(0, 1)));
+        }
+
+        // A whole game can be played
+        let config = SearchConfig {
+            depth: 2,
+            determinizations: 2,
+            width: Some(4),
+            backup: Backup::Paranoid,
+        };
+        let evaluator = TurnsToEmpty::new(default_set_map());
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(SearchStrategy::new(
+                evaluator,
+                default_set_map(),
+                config.clone(),
+                0,
+            )),
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        let options = GameOptions {
+            seed: Some(3),
+            ..Default::default()
+        };
+        assert!(run_with(&mut strategies, options).is_ok());
+
+        // Later in a round, hands are sampled without the captured cards
+        let set_map = default_set_map();
+        let mut game = GameState::from_seed(3, 1).unwrap();
+        let mut greedy = Greedy(set_map.clone());
+        while game.as_view().captured_cards.len() < 3 {
+            let action = greedy.get_action(&game.as_view()).unwrap();
+            game.apply(&action);
+        }
+        let view = game.as_view();
+        let tracker = CardTracker::new(3).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let state = tracker.determinize(&view, &mut rng);
+        for seat in 1..3 {
+            assert_eq!(state.view_for(seat).hand.len(), view.hand_sizes[seat]);
+        }
+        assert_eq!(state.view_for(1).captured_cards, view.captured_cards);
+        let mut search = SearchStrategy::new(
+            TurnsToEmpty::new(set_map.clone()),
+            set_map.clone(),
+            config.clone(),
+            0,
+        );
+        let action = search.get_action(&view).unwrap();
+        assert!(get_valid_actions(&view, &set_map).contains(&action));
+
+        // So can a smaller game, sampling hands from its deck
+        let rules = RuleSet::mini(4, 4);
+        let evaluator = TurnsToEmpty::new(rules.set_map());
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(SearchStrategy::with_rules(
+                evaluator,
+                rules.clone(),
+                config,
+                0,
+            )),
+            Box::new(Greedy(rules.set_map())),
+        ];
+        let options = GameOptions {
+            rules: Some(rules),
+            ..Default::default()
+        };
+        assert!(run_with(&mut strategies, options).is_ok());
+    }
+}
@@ -0,0 +1,412 @@
+//! Sequential probability ratio tests for comparing a candidate strategy with a baseline.
+//!
+//! Games are played head to head in duplicate pairs (see `duplicate::run_duplicate`), so each
+//! strategy plays both hands of every deal. The two games of a deal share their cards, so they
+//! are scored together as one sample. After each batch the generalised log-likelihood ratio
+//! of the two hypotheses is updated, and the test stops as soon as it crosses a bound.
+//!
+//! The win rate is tested on the pentanomial distribution of deal scores, with the maximum
+//! likelihood method of fishtest (`server/fishtest/stat_util.py` in
+//! `official-stockfish/fishtest`). Score margins aren't limited to a few outcomes, so they are
+//! tested with a normal approximation once enough deals have been played.
+
+use crate::duplicate::run_duplicate;
+use crate::rules::RuleSet;
+use crate::{GameError, Strategy};
+use std::fmt;
+
+/// What is measured per game, and the two hypotheses tested.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// Game score of 1 for a win, 0.5 for a draw and 0 for a loss, averaged over the two games
+    /// of each deal. The candidate is tested for being `elo1` stronger than the baseline,
+    /// against being `elo0` stronger.
+    WinRate { elo0: f64, elo1: f64 },
+    /// Candidate's final score minus the baseline's, averaged over the two games of each deal.
+    /// The mean margin is tested for being `margin1`, against being `margin0`.
+    ScoreMargin { margin0: f64, margin1: f64 },
+}
+
+/// Options for `sprt`.
+#[derive(Clone, Debug)]
+pub struct SprtConfig {
+    pub metric: Metric,
+    /// Probability of accepting the better hypothesis when the worse one is true
+    pub alpha: f64,
+    /// Probability of accepting the worse hypothesis when the better one is true
+    pub beta: f64,
+    /// Number of duplicate deals (two games each) played between tests
+    pub batch: usize,
+    /// Number of games after which the test gives up without a verdict
+    pub max_games: usize,
+    /// Seed for the first deal, with each later deal using the next seed
+    pub seed: u64,
+    /// Whether to print the state of the test after each batch
+    pub progress: bool,
+}
+
+impl Default for SprtConfig {
+    fn default() -> Self {
+        SprtConfig {
+            metric: Metric::WinRate {
+                elo0: 0.0,
+                elo1: 20.0,
+            },
+            alpha: 0.05,
+            beta: 0.05,
+            batch: 10,
+            max_games: 20_000,
+            seed: 0,
+            progress: false,
+        }
+    }
+}
+
+/// Outcome of a test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The better hypothesis was accepted
+    Better,
+    /// The worse hypothesis was accepted
+    NotBetter,
+    /// `max_games` were played without reaching either bound
+    Inconclusive,
+}
+
+/// State of a test, returned once it stops.
+#[derive(Clone, Debug)]
+pub struct SprtResult {
+    pub verdict: Verdict,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// Number of deals in which the candidate's mean game score was 0, 1/4, 1/2, 3/4 and 1
+    pub pentanomial: [usize; 5],
+    /// Log-likelihood ratio of the better hypothesis against the worse
+    pub llr: f64,
+    /// Lower and upper bounds on `llr`, at which the test stops
+    pub bounds: (f64, f64),
+    /// Mean score margin of the candidate over the baseline
+    pub margin: f64,
+    /// Elo difference of the candidate over the baseline, estimated from the game scores
+    pub elo: f64,
+    /// Half width of the 95% confidence interval for `elo`
+    pub elo_error: f64,
+}
+
+impl fmt::Display for SprtResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verdict = match self.verdict {
+            Verdict::Better => "better",
+            Verdict::NotBetter => "not better",
+            Verdict::Inconclusive => "inconclusive",
+        };
+        write!(
+            f,
+            "{}: Elo {:+.1} ± {:.1}, margin {:+.2} (+{} ={} -{}, LLR {:.2} [{:.2}, {:.2}], {} games)",
+            verdict,
+            self.elo,
+            self.elo_error,
+            self.margin,
+            self.wins,
+            self.draws,
+            self.losses,
+            self.llr,
+            self.bounds.0,
+            self.bounds.1,
+            self.games
+        )
+    }
+}
+
+/// Expected game score of a player `elo` stronger than their opponent.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Elo difference for an expected game score, which is infinite for a score of 0 or 1.
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Mean and variance of a sample.
+fn mean_variance(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance)
+}
+
+/// Count each deal score, which is a multiple of 1/4 as it is the mean of two game scores.
+fn pentanomial(deals: &[f64]) -> [usize; 5] {
+    let mut counts = [0; 5];
+    for score in deals {
+        counts[(score * 4.0).round() as usize] += 1;
+    }
+    counts
+}
+
+/// Count given to outcomes which haven't occurred, as in fishtest. Without it the most likely
+/// distribution with a given mean could put weight on outcomes that haven't been seen.
+const REGULARISATION: f64 = 1e-3;
+
+/// Lagrange multiplier of the distribution over `outcomes` with mean `mean` which maximises the
+/// likelihood of `counts`. That distribution is `p_i = q_i / (1 + theta (x_i - mean))`, where
+/// `q_i` are the observed frequencies, and `theta` is the root of the decreasing function
+/// found by bisection here. Every outcome must have a positive count, and `mean` must lie
+/// strictly between the smallest and largest outcome.
+fn lagrange_multiplier(outcomes: &[f64], counts: &[f64], mean: f64) -> f64 {
+    let excess = |theta: f64| {
+        outcomes
+            .iter()
+            .zip(counts)
+            .map(|(x, count)| count * (x - mean) / (1.0 + theta * (x - mean)))
+            .sum::<f64>()
+    };
+    let (low, high) = outcomes
+        .iter()
+        .fold((f64::MIN, f64::MAX), |(low, high), x| match x - mean {
+            d if d > 0.0 => (low.max(-1.0 / d), high),
+            d if d < 0.0 => (low, high.min(-1.0 / d)),
+            _ => (low, high),
+        });
+    let (mut low, mut high) = (low, high);
+    for _ in 0..200 {
+        let theta = (low + high) / 2.0;
+        match excess(theta) > 0.0 {
+            true => low = theta,
+            false => high = theta,
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Generalised SPRT log-likelihood ratio of a mean deal score of `s1` against `s0`, from the
+/// `pentanomial` counts of deal scores. Each hypothesis is represented by the distribution
+/// with its mean which is most likely to have produced the counts.
+///
+/// Unlike a normal approximation this needs no floor on the variance: a deal won outright only
+/// adds `ln(s1 / s0)` to the ratio, however few deals have been played.
+fn pentanomial_llr(pentanomial: &[usize; 5], s0: f64, s1: f64) -> f64 {
+    let outcomes = [0.0, 0.25, 0.5, 0.75, 1.0];
+    let counts = pentanomial.map(|count| (count as f64).max(REGULARISATION));
+    let (theta0, theta1) = (
+        lagrange_multiplier(&outcomes, &counts, s0),
+        lagrange_multiplier(&outcomes, &counts, s1),
+    );
+    outcomes
+        .iter()
+        .zip(counts)
+        .map(|(x, count)| count * ((1.0 + theta0 * (x - s0)).ln() - (1.0 + theta1 * (x - s1)).ln()))
+        .sum()
+}
+
+/// Number of deals played before the score margin is tested, so that its mean is close to
+/// normally distributed and its variance can be estimated.
+const MIN_DEALS: usize = 30;
+
+/// Generalised SPRT log-likelihood ratio of mean `h1` against `h0`, using a normal
+/// approximation to the distribution of `samples` with their unbiased sample variance. This is
+/// zero until `MIN_DEALS` samples have been taken.
+fn normal_llr(samples: &[f64], h0: f64, h1: f64) -> f64 {
+    if samples.len() < MIN_DEALS {
+        return 0.0;
+    }
+    let n = samples.len() as f64;
+    let (mean, variance) = mean_variance(samples);
+    let variance = variance * n / (n - 1.0);
+    n * (h1 - h0) * (2.0 * mean - h0 - h1) / (2.0 * variance)
+}
+
+/// Summarise the games played so far, from the game scores of every game and the score and
+/// margin of every deal.
+fn summarise(
+    games: &[f64],
+    deals: &[f64],
+    margins: &[f64],
+    llr: f64,
+    bounds: (f64, f64),
+) -> SprtResult {
+    let count = |score| games.iter().filter(|&&s| s == score).count();
+    let pentanomial = pentanomial(deals);
+    let (score, variance) = mean_variance(deals);
+    let error = 1.96 * (variance / deals.len() as f64).sqrt();
+    let clamp = |score: f64| score.clamp(1e-6, 1.0 - 1e-6);
+    let verdict = match llr {
+        llr if llr >= bounds.1 => Verdict::Better,
+        llr if llr <= bounds.0 => Verdict::NotBetter,
+        _ => Verdict::Inconclusive,
+    };
+    SprtResult {
+        verdict,
+        games: games.len(),
+        wins: count(1.0),
+        draws: count(0.5),
+        losses: count(0.0),
+        pentanomial,
+        llr,
+        bounds,
+        margin: mean_variance(margins).0,
+        elo: elo(clamp(score)),
+        elo_error: (elo(clamp(score + error)) - elo(clamp(score - error))) / 2.0,
+    }
+}
+
+/// Play `strategies[0]` (the candidate) against `strategies[1]` (the baseline) under `rules`
+/// in batches of duplicate deals until the test reaches a verdict, or `config.max_games` have
+/// been played. `RuleSet::two_player` gives the official rules.
+///
+/// Returns the first `GameError` if a game is halted, or `GameError::PlayerCount` if `rules`
+/// don't support two players. Panics unless there are exactly two strategies.
+pub fn sprt(
+    strategies: &mut [Box<dyn Strategy>],
+    rules: &RuleSet,
+    config: &SprtConfig,
+) -> Result<SprtResult, GameError> {
+    assert_eq!(
+        strategies.len(),
+        2,
+        "A test needs a candidate and a baseline"
+    );
+    let (h0, h1) = match config.metric {
+        Metric::WinRate { elo0, elo1 } => (expected_score(elo0), expected_score(elo1)),
+        Metric::ScoreMargin { margin0, margin1 } => (margin0, margin1),
+    };
+    let bounds = (
+        (config.beta / (1.0 - config.alpha)).ln(),
+        ((1.0 - config.beta) / config.alpha).ln(),
+    );
+
+    let mut games = Vec::new();
+    let mut scores = Vec::new();
+    let mut margins = Vec::new();
+    let mut seed = config.seed;
+    loop {
+        for _ in 0..config.batch.max(1) {
+            let deal = run_duplicate(strategies, rules, seed)?;
+            seed += 1;
+            let mut margin = 0.0;
+            let mut score = 0.0;
+            for rotation in 0..2 {
+                let game_margin = deal.scores[0][rotation] - deal.scores[1][rotation];
+                let game_score = match game_margin {
+                    m if m > 0 => 1.0,
+                    0 => 0.5,
+                    _ => 0.0,
+                };
+                games.push(game_score);
+                margin += game_margin as f64 / 2.0;
+                score += game_score / 2.0;
+            }
+            margins.push(margin);
+            scores.push(score);
+        }
+        let llr = match config.metric {
+            Metric::WinRate { .. } => pentanomial_llr(&pentanomial(&scores), h0, h1),
+            Metric::ScoreMargin { .. } => normal_llr(&margins, h0, h1),
+        };
+        let result = summarise(&games, &scores, &margins, llr, bounds);
+        if config.progress {
+            println!("{}", result);
+        }
+        if result.verdict != Verdict::Inconclusive || result.games >= config.max_games {
+            return Ok(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Greedy;
+    use crate::{default_set_map, get_valid_actions, Action, GameView, SetMap};
+
+    /// Plays the first valid action, which scouts whenever it can
+    struct First(SetMap);
+
+    impl Strategy for First {
+        fn get_action(&mut self, view: &GameView) -> Option<Action> {
+            get_valid_actions(view, &self.0).first().copied()
+        }
+    }
+
+    #[test]
+    fn test_sprt() {
+        assert!((elo(expected_score(35.0)) - 35.0).abs() < 1e-9);
+
+        // A deal won outright adds ln(s1 / s0) to the ratio, so a single win is weak evidence
+        // and a run of them crosses the upper bound after ln(19) / ln(s1 / s0) deals
+        let (s0, s1) = (expected_score(0.0), expected_score(20.0));
+        let bounds = (-(19f64.ln()), 19f64.ln());
+        let won = |deals| pentanomial_llr(&[0, 0, 0, 0, deals], s0, s1);
+        let lost = |deals| pentanomial_llr(&[deals, 0, 0, 0, 0], s0, s1);
+        assert!((won(1) - (s1 / s0).ln()).abs() < 1e-3);
+        assert!((lost(1) - ((1.0 - s1) / (1.0 - s0)).ln()).abs() < 1e-3);
+        let needed = (bounds.1 / (s1 / s0).ln()).ceil() as usize;
+        assert!(won(needed - 1) < bounds.1 && won(needed) >= bounds.1);
+        let needed = (bounds.0 / ((1.0 - s1) / (1.0 - s0)).ln()).ceil() as usize;
+        assert!(lost(needed - 1) > bounds.0 && lost(needed) <= bounds.0);
+
+        // Evenly matched deals favour the worse hypothesis, and before any deal the ratio is
+        // close to zero
+        assert!(pentanomial_llr(&[10, 10, 10, 10, 10], s0, s1) < 0.0);
+        assert!(pentanomial_llr(&[0; 5], s0, s1).abs() < 1e-2);
+
+        // Margins are only tested once there are enough deals
+        assert_eq!(normal_llr(&[5.0; MIN_DEALS - 1], 0.0, 1.0), 0.0);
+        let margins: Vec<f64> = (0..MIN_DEALS).map(|i| (i % 3) as f64).collect();
+        assert!(normal_llr(&margins, 0.0, 1.0) > 0.0);
+        assert!(normal_llr(&margins, 1.0, 2.0) < 0.0);
+
+        // The test stops on reaching either bound
+        let result = summarise(&[1.0; 20], &[1.0; 10], &[5.0; 10], bounds.1, bounds);
+        assert_eq!(result.verdict, Verdict::Better);
+        assert_eq!((result.wins, result.losses, result.games), (20, 0, 20));
+        assert_eq!(result.pentanomial, [0, 0, 0, 0, 10]);
+        let result = summarise(&[0.0; 2], &[0.0], &[-5.0], bounds.0, bounds);
+        assert_eq!(result.verdict, Verdict::NotBetter);
+        let result = summarise(&[0.5; 2], &[0.5], &[0.0], bounds.1 - 1e-9, bounds);
+        assert_eq!(result.verdict, Verdict::Inconclusive);
+
+        // A much stronger candidate is accepted quickly
+        let two_player = RuleSet::two_player();
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(default_set_map())),
+            Box::new(First(default_set_map())),
+        ];
+        let result = sprt(&mut strategies, &two_player, &SprtConfig::default()).unwrap();
+        assert_eq!(result.verdict, Verdict::Better);
+        assert!(result.games < 200);
+        assert!(result.elo > 0.0 && result.margin > 0.0);
+        assert!(result.to_string().starts_with("better: Elo +"));
+
+        // A weaker one is rejected on either metric
+        strategies.swap(0, 1);
+        let config = SprtConfig {
+            metric: Metric::ScoreMargin {
+                margin0: 0.0,
+                margin1: 1.0,
+            },
+            ..Default::default()
+        };
+        let result = sprt(&mut strategies, &two_player, &config).unwrap();
+        assert_eq!(result.verdict, Verdict::NotBetter);
+        assert!(result.margin < 0.0);
+
+        // Running out of games gives no verdict, here on a smaller variant
+        let rules = RuleSet::mini(4, 4);
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(rules.set_map())),
+            Box::new(Greedy(rules.set_map())),
+        ];
+        let config = SprtConfig {
+            batch: 2,
+            max_games: 4,
+            ..Default::default()
+        };
+        let result = sprt(&mut strategies, &rules, &config).unwrap();
+        assert_eq!(result.verdict, Verdict::Inconclusive);
+        assert_eq!(result.games, 4);
+    }
+}
@@ -0,0 +1,312 @@
+//! Statistics collected from simulated games.
+//!
+//! A `StatsCollector` is an `Observer`, so it can be passed to `run_with` in `GameOptions::observer` to record each
+//! game as it is played. Games accumulate across calls, so batches can be summarised together
+//! or exported to CSV with one row per game.
+
+use crate::env::MAX_PLAYERS;
+use crate::{Action, GameResult, GameState, Observer, RoundEnd};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Metrics for a single completed game. Vectors are indexed by seat.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameStats {
+    pub scouts: Vec<usize>,
+    pub shows: Vec<usize>,
+    pub scout_shows: Vec<usize>,
+    /// Length of the longest set shown
+    pub largest_set: usize,
+    /// Mean length of the active set when each action was chosen
+    pub mean_active: f64,
+    /// Number of actions taken before each scout and show, in order
+    pub scout_show_turns: Vec<usize>,
+    /// Number of actions taken in the round
+    pub turns: usize,
+    pub round_end: Option<RoundEnd>,
+    pub scores: Vec<i32>,
+}
+
+impl GameStats {
+    fn new(players: usize) -> GameStats {
+        GameStats {
+            scouts: vec![0; players],
+            shows: vec![0; players],
+            scout_shows: vec![0; players],
+            ..Default::default()
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        self.scores.len()
+    }
+}
+
+/// Totals over the collected games.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatsSummary {
+    pub games: usize,
+    /// Mean number of each kind of action per game, by seat
+    pub scouts: Vec<f64>,
+    pub shows: Vec<f64>,
+    pub scout_shows: Vec<f64>,
+    /// Longest set shown in any game, and the mean over games of the longest set
+    pub largest_set: usize,
+    pub mean_largest_set: f64,
+    /// Mean length of the active set when an action was chosen
+    pub mean_active: f64,
+    /// Fraction of actions which were a scout and show
+    pub scout_show_rate: f64,
+    /// Mean point in the round at which scout and shows were played, from 0 at the first action
+    /// to 1 at the last
+    pub scout_show_timing: f64,
+    /// Mean number of actions per round
+    pub mean_turns: f64,
+    pub hand_emptied: usize,
+    pub all_scouted: usize,
+}
+
+/// Records statistics of every completed game it observes.
+#[derive(Debug, Default)]
+pub struct StatsCollector {
+    games: Vec<GameStats>,
+    current: Option<GameStats>,
+    active_total: usize,
+}
+
+impl StatsCollector {
+    pub fn new() -> StatsCollector {
+        StatsCollector::default()
+    }
+
+    /// Statistics of each completed game, in the order played.
+    pub fn games(&self) -> &[GameStats] {
+        &self.games
+    }
+
+    /// Summarise every game collected so far.
+    pub fn summary(&self) -> StatsSummary {
+        let games = self.games.len();
+        if games == 0 {
+            return StatsSummary::default();
+        }
+        let seats = self.games.iter().map(GameStats::players).max().unwrap();
+        let per_seat = |count: fn(&GameStats) -> &Vec<usize>| -> Vec<f64> {
+            (0..seats)
+                .map(|seat| {
+                    let played: Vec<usize> = self
+                        .games
+                        .iter()
+                        .filter_map(|game| count(game).get(seat).copied())
+                        .collect();
+                    played.iter().sum::<usize>() as f64 / played.len() as f64
+                })
+                .collect()
+        };
+        let mean =
+            |value: fn(&GameStats) -> f64| self.games.iter().map(value).sum::<f64>() / games as f64;
+        let turns: usize = self.games.iter().map(|game| game.turns).sum();
+        let scout_show_turns: Vec<f64> = self
+            .games
+            .iter()
+            .flat_map(|game| {
+                let last = game.turns.saturating_sub(1).max(1) as f64;
+                game.scout_show_turns
+                    .iter()
+                    .map(move |&turn| turn as f64 / last)
+            })
+            .collect();
+        let ends = |end| {
+            self.games
+                .iter()
+                .filter(|game| game.round_end == Some(end))
+                .count()
+        };
+        StatsSummary {
+            games,
+            scouts: per_seat(|game| &game.scouts),
+            shows: per_seat(|game| &game.shows),
+            scout_shows: per_seat(|game| &game.scout_shows),
+            largest_set: self
+                .games
+                .iter()
+                .map(|game| game.largest_set)
+                .max()
+                .unwrap(),
+            mean_largest_set: mean(|game| game.largest_set as f64),
+            mean_active: self
+                .games
+                .iter()
+                .map(|game| game.mean_active * game.turns as f64)
+                .sum::<f64>()
+                / turns.max(1) as f64,
+            scout_show_rate: scout_show_turns.len() as f64 / turns.max(1) as f64,
+            scout_show_timing: match scout_show_turns.is_empty() {
+                true => 0.0,
+                false => scout_show_turns.iter().sum::<f64>() / scout_show_turns.len() as f64,
+            },
+            mean_turns: turns as f64 / games as f64,
+            hand_emptied: ends(RoundEnd::HandEmptied),
+            all_scouted: ends(RoundEnd::AllScouted),
+        }
+    }
+
+    /// Write one row per game to `path`, with a header. Per seat columns are repeated for
+    /// `MAX_PLAYERS` seats, and left empty for seats a game didn't have. Scout and show turns
+    /// are separated by spaces.
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut header = vec![
+            "game".to_owned(),
+            "players".to_owned(),
+            "turns".to_owned(),
+            "round_end".to_owned(),
+            "largest_set".to_owned(),
+            "mean_active".to_owned(),
+            "scout_show_turns".to_owned(),
+        ];
+        for column in ["score", "scouts", "shows", "scout_shows"] {
+            header.extend((0..MAX_PLAYERS).map(|seat| format!("{}_{}", column, seat)));
+        }
+        writeln!(writer, "{}", header.join(","))?;
+
+        for (i, game) in self.games.iter().enumerate() {
+            let turns: Vec<String> = game.scout_show_turns.iter().map(usize::to_string).collect();
+            let round_end = match game.round_end {
+                Some(RoundEnd::HandEmptied) => "hand_emptied",
+                Some(RoundEnd::AllScouted) => "all_scouted",
+                None => "",
+            };
+            let mut row = vec![
+                i.to_string(),
+                game.players().to_string(),
+                game.turns.to_string(),
+                round_end.to_owned(),
+                game.largest_set.to_string(),
+                game.mean_active.to_string(),
+                turns.join(" "),
+            ];
+            let mut seats = |values: Vec<String>| {
+                row.extend(
+                    (0..MAX_PLAYERS).map(|seat| values.get(seat).cloned().unwrap_or_default()),
+                )
+            };
+            seats(game.scores.iter().map(i32::to_string).collect());
+            seats(game.scouts.iter().map(usize::to_string).collect());
+            seats(game.shows.iter().map(usize::to_string).collect());
+            seats(game.scout_shows.iter().map(usize::to_string).collect());
+            writeln!(writer, "{}", row.join(","))?;
+        }
+        writer.flush()
+    }
+}
+
+impl Observer for StatsCollector {
+    fn on_start(&mut self, game: &GameState) {
+        // Any game in progress was halted, so is dropped
+        self.current = Some(GameStats::new(game.game_size));
+        self.active_total = 0;
+    }
+
+    fn on_action(&mut self, game: &GameState, seat: usize, action: &Action) {
+        let Some(stats) = self.current.as_mut() else {
+            return;
+        };
+        self.active_total += game.active.len();
+        let shown = match *action {
+            Action::Scout(..) => {
+                stats.scouts[seat] += 1;
+                return;
+            }
+            Action::Show(start, stop) => {
+                stats.shows[seat] += 1;
+                stop - start + 1
+            }
+            Action::ScoutShow(.., start, stop) => {
+                stats.scout_shows[seat] += 1;
+                stats.scout_show_turns.push(game.turns);
+                stop - start + 1
+            }
+        };
+        stats.largest_set = stats.largest_set.max(shown);
+    }
+
+    fn on_result(&mut self, result: &GameResult) {
+        let Some(mut stats) = self.current.take() else {
+            return;
+        };
+        stats.turns = result.turns;
+        stats.round_end = Some(result.round_end);
+        stats.scores = result.scores.clone();
+        stats.mean_active = self.active_total as f64 / result.turns.max(1) as f64;
+        self.games.push(stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+    use crate::tests::{Fixed, Greedy};
+    use crate::{default_set_map, run_with, GameOptions, Strategy};
+    use std::{env, fs};
+
+    #[test]
+    fn test_stats() {
+        let mut collector = StatsCollector::new();
+        let rules = RuleSet::default();
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+            Box::new(Greedy(default_set_map())),
+        ];
+        for seed in 0..5 {
+            let options = GameOptions {
+                rules: Some(rules.clone()),
+                seed: Some(seed),
+                observer: Some(&mut collector),
+                ..Default::default()
+            };
+            run_with(&mut strategies, options).unwrap();
+        }
+
+        // Halted games aren't recorded
+        let mut resigning: Vec<Box<dyn Strategy>> = vec![
+            Box::new(Fixed(Some(Action::Show(0, 0)))),
+            Box::new(Fixed(None)),
+            Box::new(Fixed(None)),
+        ];
+        let options = GameOptions {
+            rules: Some(rules),
+            seed: Some(0),
+            observer: Some(&mut collector),
+            ..Default::default()
+        };
+        assert!(run_with(&mut resigning, options).is_err());
+
+        let games = collector.games();
+        assert_eq!(games.len(), 5);
+        for game in games {
+            let actions: usize = (0..3)
+                .map(|seat| game.scouts[seat] + game.shows[seat] + game.scout_shows[seat])
+                .sum();
+            assert_eq!(actions, game.turns);
+            assert!(game.largest_set >= 1);
+        }
+
+        let summary = collector.summary();
+        assert_eq!(summary.games, 5);
+        assert_eq!(summary.hand_emptied + summary.all_scouted, 5);
+        let turns: usize = games.iter().map(|game| game.turns).sum();
+        assert_eq!(summary.mean_turns, turns as f64 / 5.0);
+        assert!((0.0..=1.0).contains(&summary.scout_show_timing));
+
+        let path = env::temp_dir().join(format!("scout-stats-{}.csv", std::process::id()));
+        collector.write_csv(&path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(csv.lines().count(), 6);
+        assert!(csv.starts_with("game,players,turns,round_end"));
+    }
+}
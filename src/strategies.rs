@@ -1,49 +1,9 @@
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+use scout_game::analysis::{explain_comparison, HandAnalyser};
 use scout_game::{get_valid_actions, Action, GameView, NewGameView, SetMap, Strategy};
-use std::collections::HashMap;
 use std::io;
 
-/// Returns minimum number of show actions required to empty hand.
-/// This iterates through all possible sets, checks validity against `set_map`,
-/// then evaluates remaining hand recursively.
-fn turns_to_empty(
-    hand: &Vec<i32>,
-    set_map: &SetMap,
-    cache: &mut HashMap<Vec<i32>, usize>,
-) -> usize {
-    if set_map.contains_key(hand) {
-        return 1;
-    }
-
-    let turns = match cache.get(hand) {
-        Some(n) => return *n,
-        None => (0..hand.len())
-            .flat_map(|start| (start..hand.len()).map(move |stop| (start..stop + 1)))
-            .map(|range| {
-                let mut new_hand = hand.clone();
-                let set: Vec<i32> = new_hand.drain(range).collect();
-                (set, new_hand)
-            })
-            .filter(|(set, _)| set_map.contains_key(set))
-            .map(
-                |(_, new_hand)| match turns_to_empty(&new_hand, &set_map, cache) {
-                    1 => {
-                        cache.insert(hand.clone(), 2);
-                        return 2; // Return early
-                    }
-                    x => x + 1,
-                },
-            )
-            .min()
-            .unwrap(),
-    };
-
-    // Cache!
-    cache.insert(hand.clone(), turns);
-    return turns;
-}
-
 /// Strategy which requests user for input
 /// When prompted for an action, enter one of the following actions:
 /// - `scout [left] [flip] [index]`
@@ -105,8 +65,7 @@ impl Strategy for GetPlayerAction {
                 // Scout and show should never end round - halt if this happens
                 // TODO: create modified copy of view to prevent scout round end condition
                 let scout_view = match view.take_action(&scout) {
-                    NewGameView::Win => return None,
-                    NewGameView::Loss => return None,
+                    NewGameView::Win | NewGameView::Draw | NewGameView::Loss => return None,
                     NewGameView::Continue(view) => view,
                 };
 
@@ -145,50 +104,100 @@ impl Strategy for GetPlayerAction {
             "quit" => return None,
             _ => {
                 println!("Input not accepted! Enter: scout, show, scoutshow, or quit");
-                return self.get_action(&view);
+                return self.get_action(view);
             }
         };
-        if get_valid_actions(&view, &self.set_map).contains(&action) {
-            return Some(action);
+        if get_valid_actions(view, &self.set_map).contains(&action) {
+            Some(action)
         } else {
-            println!("Not a valid action!");
-            return self.get_action(&view);
+            println!(
+                "Not a valid action! {}",
+                explain_invalid(view, &action, &self.set_map)
+            );
+            self.get_action(view)
         }
     }
 }
 
+/// Describe why `action`, which is known to be invalid, can't be played from `view`.
+fn explain_invalid(view: &GameView, action: &Action, set_map: &SetMap) -> String {
+    let (left, flip, index) = match *action {
+        Action::Scout(left, flip, index) | Action::ScoutShow(left, flip, index, _, _) => {
+            (left, flip, index)
+        }
+        Action::Show(start, stop) => return explain_show(view, start, stop, set_map),
+    };
+    if view.active.is_empty() {
+        return "There is no active set to scout from".to_owned();
+    }
+    if index > view.hand.len() {
+        return format!("Can't insert a card at index {}", index);
+    }
+    match *action {
+        Action::ScoutShow(.., start, stop) if view.scout_show[0] > 0 => {
+            let mut scout_view = view.clone();
+            scout_view.apply(&Action::Scout(left, flip, index));
+            explain_show(&scout_view, start, stop, set_map)
+        }
+        Action::ScoutShow(..) => "Scout and show has already been used this round".to_owned(),
+        _ => "Unknown reason".to_owned(),
+    }
+}
+
+/// Describe why the set from `start` to `stop` can't be shown over the active set.
+fn explain_show(view: &GameView, start: usize, stop: usize, set_map: &SetMap) -> String {
+    if start > stop || stop >= view.hand.len() {
+        return format!("Cards {} to {} are not in your hand", start, stop);
+    }
+    let set = &view.hand[start..stop + 1];
+    let active: Vec<i32> = view.active.iter().map(|card| card.top()).collect();
+    format!(
+        "{:?} doesn't beat {:?}: {}",
+        set,
+        active,
+        explain_comparison(set, &active, set_map)
+    )
+}
+
 /// Simple strategy which simply minimises the number of show turns required to
 /// empty the current hand. This results in aggressive rush plays, and is especially
 /// weak to mid-game large sets.
 pub struct StrategyRush {
     set_map: SetMap,
-    cache: HashMap<Vec<i32>, usize>,
+    analyser: HandAnalyser,
 }
 
 impl StrategyRush {
     pub fn new() -> StrategyRush {
         StrategyRush {
             set_map: scout_game::default_set_map(),
-            cache: HashMap::new(),
+            analyser: HandAnalyser::new(scout_game::default_set_map()),
         }
     }
 }
 
 impl Strategy for StrategyRush {
     fn get_action(&mut self, view: &GameView) -> Option<Action> {
-        let mut actions = get_valid_actions(&view, &self.set_map);
+        let mut actions = get_valid_actions(view, &self.set_map);
         actions.shuffle(&mut thread_rng());
 
-        let mut cache = self.cache.clone();
-
-        actions.sort_by_key(|action| match view.take_action(action) {
-            NewGameView::Continue(new) => turns_to_empty(&new.hand, &self.set_map, &mut cache) + 1,
-            NewGameView::Win => 0,
-            NewGameView::Loss => 32,
-        });
+        // Evaluate each action in place on a single working copy of the view
+        let mut view = view.clone();
+        let mut keys = Vec::with_capacity(actions.len());
+        for action in &actions {
+            let undo = view.apply(action);
+            keys.push(match view.outcome() {
+                None => self.analyser.turns_to_empty(&view.hand) + 1,
+                Some(NewGameView::Win) => 0,
+                Some(NewGameView::Draw) => 1,
+                Some(_) => 32,
+            });
+            view.undo(undo);
+        }
 
-        self.cache = cache;
-        return Some(actions[0]);
+        (0..actions.len())
+            .min_by_key(|&i| keys[i])
+            .map(|i| actions[i])
     }
 }
 
@@ -225,29 +234,3 @@ impl Strategy for StrategyRush {
 //             .copied();
 //     }
 // }
-
-#[test]
-fn test_turns_to_empty() {
-    let set_map = scout_game::default_set_map();
-    let mut cache: HashMap<Vec<i32>, usize> = HashMap::new();
-
-    // Trivial cases
-    assert_eq!(turns_to_empty(&vec![0], &set_map, &mut cache), 1);
-    assert_eq!(turns_to_empty(&vec![0, 1, 2], &set_map, &mut cache), 1);
-
-    // Fiddly examples
-    assert_eq!(turns_to_empty(&vec![0, 1, 0], &set_map, &mut cache), 2);
-    assert_eq!(turns_to_empty(&vec![1, 3, 5], &set_map, &mut cache), 3);
-    assert_eq!(turns_to_empty(&vec![1, 3, 1], &set_map, &mut cache), 2);
-    assert_eq!(turns_to_empty(&vec![1, 3, 3, 1], &set_map, &mut cache), 2);
-    assert_eq!(
-        turns_to_empty(&vec![1, 3, 5, 7, 1], &set_map, &mut cache),
-        4
-    );
-
-    // Big hands
-    assert_eq!(
-        turns_to_empty(&vec![7, 3, 2, 1, 4, 7, 1, 2, 1], &set_map, &mut cache),
-        5
-    );
-}
@@ -0,0 +1,209 @@
+//! Tracking of unseen cards, and estimates of which sets opponents could beat.
+
+use crate::analysis::find_sets;
+use crate::rules::RuleSet;
+use crate::{Card, GameError, GameState, GameView, Player, SetMap};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+
+/// Identifies a card regardless of which way up it is.
+fn identity(card: &Card) -> (i32, i32) {
+    (card.0.min(card.1), card.0.max(card.1))
+}
+
+/// Tracks the cards a player hasn't seen. These are worked out from the deck, the player's own
+/// hand, the active set, and the cards captured so far, which every view carries.
+pub struct CardTracker {
+    deck: Vec<Card>,
+    rules: RuleSet,
+}
+
+impl CardTracker {
+    /// Create a tracker for a game with the official rules for `game_size` players, or
+    /// `GameError::PlayerCount` if there are none.
+    pub fn new(game_size: usize) -> Result<CardTracker, GameError> {
+        let rules = RuleSet::official(game_size).ok_or(GameError::PlayerCount(game_size))?;
+        Ok(CardTracker::with_rules(game_size, &rules))
+    }
+
+    /// Create a tracker for a game of `game_size` players with the given `rules`.
+    pub fn with_rules(game_size: usize, rules: &RuleSet) -> CardTracker {
+        CardTracker {
+            deck: rules.deck(game_size),
+            rules: rules.clone(),
+        }
+    }
+
+    /// Cards which could be in an opponent's hand, the way up they were dealt.
+    pub fn unseen(&self, view: &GameView) -> Vec<Card> {
+        let mut seen: HashSet<(i32, i32)> = view.captured_cards.iter().map(identity).collect();
+        seen.extend(view.active.iter().map(identity));
+        seen.extend(
+            view.hand
+                .iter()
+                .zip(&view.hand_bottoms)
+                .map(|(&top, &bottom)| identity(&Card(top, bottom))),
+        );
+        self.deck
+            .iter()
+            .filter(|card| !seen.contains(&identity(card)))
+            .cloned()
+            .collect()
+    }
+
+    /// Estimate the probability that each player in `view` holds a contiguous set which beats
+    /// `set`. Opponents' hands are sampled `samples` times from the unseen cards, while the
+    /// viewer (index 0) is checked exactly.
+    pub fn beat_probabilities<R: Rng>(
+        &self,
+        view: &GameView,
+        set: &[i32],
+        set_map: &SetMap,
+        samples: usize,
+        rng: &mut R,
+    ) -> Vec<f64> {
+        let rank = set_map.get(set).copied().unwrap_or(0);
+        let beats = |hand: &[i32]| !find_sets(hand, set_map).beating(rank).is_empty();
+
+        let mut unseen = self.unseen(view);
+        let mut probabilities = vec![if beats(&view.hand) { 1.0 } else { 0.0 }];
+        for &hand_size in &view.hand_sizes[1..] {
+            let mut hits = 0;
+            for _ in 0..samples {
+                unseen.shuffle(rng);
+                let hand: Vec<i32> = unseen.iter().take(hand_size).map(|card| card.0).collect();
+                if beats(&hand) {
+                    hits += 1;
+                }
+            }
+            probabilities.push(hits as f64 / samples as f64);
+        }
+        probabilities
+    }
+
+    /// Sample a complete game consistent with `view`, dealing the unseen cards at random to the
+    /// other players. The viewer is seat 0 and next to play. Only the total score of each player
+    /// is known, so it is held as captured cards, or as tokens if captured cards don't score.
+    pub fn determinize<R: Rng>(&self, view: &GameView, rng: &mut R) -> GameState {
+        let mut unseen = self.unseen(view);
+        unseen.shuffle(rng);
+        let mut unseen = unseen.into_iter();
+        let players = (0..view.hand_sizes.len())
+            .map(|i| Player {
+                hand: match i {
+                    0 => view
+                        .hand
+                        .iter()
+                        .zip(&view.hand_bottoms)
+                        .map(|(&top, &bottom)| Card(top, bottom))
+                        .collect(),
+                    _ => unseen.by_ref().take(view.hand_sizes[i]).collect(),
+                },
+                captured: if view.scoring.captured {
+                    view.scores[i]
+                } else {
+                    0
+                },
+                tokens: if view.scoring.captured {
+                    0
+                } else {
+                    view.scores[i]
+                },
+                scout_show: view.scout_show[i],
+            })
+            .collect();
+        GameState {
+            players,
+            game_size: view.hand_sizes.len(),
+            active: view.active.clone(),
+            active_owner: view.active_owner,
+            turn: 0,
+            turns: 0,
+            round_end: view.round_end,
+            rules: RuleSet {
+                scoring: view.scoring,
+                owner_continues: view.owner_continues,
+                ..self.rules.clone()
+            },
+            captured_cards: view.captured_cards.clone(),
+            hash: 0,
+        }
+        .with_hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{default_set_map, get_valid_actions, Action, GameState};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_beat_probabilities() {
+        let set_map = default_set_map();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut game = GameState::new(3, true).unwrap();
+        let tracker = CardTracker::new(3).unwrap();
+
+        // Before any action, the unseen cards are those in other hands
+        let view = game.as_view();
+        assert_eq!(tracker.unseen(&view).len(), 24);
+        game.apply(&Action::Show(0, 0));
+
+        // The shown card is seen, while scouting keeps the card in play
+        let view = game.as_view();
+        assert_eq!(tracker.unseen(&view).len(), 23);
+        game.apply(&Action::Scout(true, false, 0));
+        assert_eq!(tracker.unseen(&game.view_for(1)).len(), 23);
+
+        // Any card beats an empty set, while nothing beats the strongest flush in this deck
+        let probabilities = tracker.beat_probabilities(&view, &[], &set_map, 20, &mut rng);
+        assert_eq!(probabilities, vec![1.0, 1.0, 1.0]);
+        let probabilities = tracker.beat_probabilities(&view, &[8; 9], &set_map, 20, &mut rng);
+        assert_eq!(probabilities, vec![0.0, 0.0, 0.0]);
+
+        // A single low card is almost certainly beaten
+        let probabilities = tracker.beat_probabilities(&view, &[0], &set_map, 20, &mut rng);
+        assert!(probabilities.iter().all(|&p| p > 0.9));
+    }
+
+    #[test]
+    fn test_captured_cards() {
+        let set_map = default_set_map();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut game = GameState::from_seed(3, 0).unwrap();
+        let tracker = CardTracker::new(3).unwrap();
+        assert!(matches!(
+            CardTracker::new(6),
+            Err(GameError::PlayerCount(6))
+        ));
+
+        // The second show captures the first, which is then out of play for everyone
+        game.apply(&Action::Show(0, 0));
+        let captured = game.active[0].clone();
+        let show = get_valid_actions(&game.as_view(), &set_map)
+            .into_iter()
+            .find(|action| matches!(action, Action::Show(..)))
+            .unwrap();
+        game.apply(&show);
+        let view = game.as_view();
+        assert_eq!(view.captured_cards, vec![captured.clone()]);
+        let unseen = tracker.unseen(&view);
+        assert_eq!(unseen.len(), view.hand_sizes[1..].iter().sum::<usize>());
+        assert!(!unseen
+            .iter()
+            .any(|card| identity(card) == identity(&captured)));
+
+        // Determinized deals never hand it back to an opponent
+        for _ in 0..10 {
+            let state = tracker.determinize(&view, &mut rng);
+            assert!(state.players.iter().all(|player| !player
+                .hand
+                .iter()
+                .any(|card| identity(card) == identity(&captured))));
+            assert_eq!(state.captured_cards, view.captured_cards);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Tuning of strategy weights by evolutionary search.
+//!
+//! A `Tuner` builds candidate strategies from weight vectors with a factory, and measures their
+//! fitness as a win rate over seeded games against a fixed set of opponents.
+
+use crate::rules::RuleSet;
+use crate::{run_with, GameError, GameOptions, Strategy};
+use rand::distributions::{Distribution, Normal};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Options for `Tuner`.
+#[derive(Clone, Debug)]
+pub struct TunerConfig {
+    /// Number of candidates in each generation
+    pub population: usize,
+    /// Number of generations `Tuner::run` evolves
+    pub generations: usize,
+    /// Number of games played to measure each candidate's win rate
+    pub games: usize,
+    /// Number of best candidates kept as parents for the next generation
+    pub elite: usize,
+    /// Standard deviation of the noise added to each weight of a child
+    pub mutation: f64,
+    /// Seed for mutations, and for the first of the deals every candidate plays, with each later
+    /// deal using the next seed
+    pub seed: u64,
+    /// File the population is saved to after each generation
+    pub checkpoint: Option<PathBuf>,
+    /// Rules to play with, or `None` for the official rules for the number of players
+    pub rules: Option<RuleSet>,
+}
+
+impl Default for TunerConfig {
+    fn default() -> Self {
+        TunerConfig {
+            population: 16,
+            generations: 20,
+            games: 50,
+            elite: 4,
+            mutation: 0.5,
+            seed: 0,
+            checkpoint: None,
+            rules: None,
+        }
+    }
+}
+
+/// Weights and the win rate they achieved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub weights: Vec<f64>,
+    pub win_rate: f64,
+}
+
+/// Genetic algorithm over strategy weights. Every candidate of every generation plays the same
+/// seeded deals, rotating through the seats, with shared wins split between the winners, so win
+/// rates from different generations can be compared. The fittest
+/// candidates are kept, and the rest of the next generation is bred from them by uniform
+/// crossover and Gaussian mutation.
+pub struct Tuner<F> {
+    factory: F,
+    opponents: Vec<Box<dyn Strategy>>,
+    config: TunerConfig,
+    generation: usize,
+    population: Vec<Vec<f64>>,
+    best: Option<Candidate>,
+}
+
+impl<F: FnMut(&[f64]) -> Box<dyn Strategy>> Tuner<F> {
+    /// Create a tuner starting from mutations of `initial`. The number of players is one more
+    /// than the number of `opponents`.
+    pub fn new(
+        factory: F,
+        opponents: Vec<Box<dyn Strategy>>,
+        initial: Vec<f64>,
+        config: TunerConfig,
+    ) -> Tuner<F> {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut population = vec![initial.clone()];
+        while population.len() < config.population {
+            population.push(mutate(&initial, config.mutation, &mut rng));
+        }
+        Tuner {
+            factory,
+            opponents,
+            config,
+            generation: 0,
+            population,
+            best: None,
+        }
+    }
+
+    /// Create a tuner from the population and fittest candidate saved in `config.checkpoint`.
+    pub fn resume(
+        factory: F,
+        opponents: Vec<Box<dyn Strategy>>,
+        config: TunerConfig,
+    ) -> io::Result<Tuner<F>> {
+        let path = config.checkpoint.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no checkpoint path given")
+        })?;
+        let (generation, best, population) = parse_checkpoint(&fs::read_to_string(path)?)?;
+        Ok(Tuner {
+            factory,
+            opponents,
+            config,
+            generation,
+            population,
+            best,
+        })
+    }
+
+    /// Number of generations evolved so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Weights of the current generation.
+    pub fn population(&self) -> &[Vec<f64>] {
+        &self.population
+    }
+
+    /// Fittest candidate seen so far, if any generation has been evaluated.
+    pub fn best(&self) -> Option<&Candidate> {
+        self.best.as_ref()
+    }
+
+    /// Measure the win rate of `weights` over the tuner's deals. A game halted
+    /// by the candidate counts as a loss, but a game halted by an opponent, or which couldn't be
+    /// dealt, returns its `GameError`.
+    pub fn evaluate(&mut self, weights: &[f64]) -> Result<f64, GameError> {
+        let games = self.config.games;
+        if games == 0 {
+            return Ok(0.0);
+        }
+        let n_players = self.opponents.len() + 1;
+        let mut wins = 0.0;
+        for game in 0..games {
+            let seed = self.config.seed + game as u64;
+            let seat = game % n_players;
+            let mut strategies = std::mem::take(&mut self.opponents);
+            strategies.insert(seat, (self.factory)(weights));
+            let options = GameOptions {
+                rules: self.config.rules.clone(),
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let result = run_with(&mut strategies, options);
+            strategies.remove(seat);
+            self.opponents = strategies;
+            match result {
+                Ok(result) if result.winners.contains(&seat) => {
+                    wins += 1.0 / result.winners.len() as f64;
+                }
+                Ok(_) => {}
+                Err(err) if err.halted().is_some_and(|halted| halted.seat == seat) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(wins / games as f64)
+    }
+
+    /// Evaluate the current generation and breed the next, saving it to the checkpoint if one is
+    /// configured. Returns the fittest candidate of the evaluated generation.
+    ///
+    /// A `GameError` from `evaluate` is returned as an `io::Error`, leaving the generation to be
+    /// evaluated again.
+    pub fn step(&mut self) -> io::Result<Candidate> {
+        let mut candidates = Vec::with_capacity(self.population.len());
+        for weights in self.population.clone() {
+            candidates.push(Candidate {
+                win_rate: self.evaluate(&weights).map_err(io::Error::other)?,
+                weights,
+            });
+        }
+        candidates.sort_by(|a, b| b.win_rate.total_cmp(&a.win_rate));
+
+        let mut rng = StdRng::seed_from_u64(self.config.seed ^ (self.generation as u64 + 1));
+        let parents = &candidates[..self.config.elite.clamp(1, candidates.len())];
+        let mut population: Vec<Vec<f64>> = parents.iter().map(|c| c.weights.clone()).collect();
+        while population.len() < self.config.population {
+            let a = &parents[rng.gen_range(0, parents.len())].weights;
+            let b = &parents[rng.gen_range(0, parents.len())].weights;
+            let child: Vec<f64> = a
+                .iter()
+                .zip(b)
+                .map(|(&a, &b)| if rng.gen() { a } else { b })
+                .collect();
+            population.push(mutate(&child, self.config.mutation, &mut rng));
+        }
+        self.population = population;
+        self.generation += 1;
+
+        let fittest = candidates.swap_remove(0);
+        if self
+            .best
+            .as_ref()
+            .is_none_or(|best| fittest.win_rate > best.win_rate)
+        {
+            self.best = Some(fittest.clone());
+        }
+        if let Some(path) = &self.config.checkpoint {
+            let text = format_checkpoint(self.generation, self.best.as_ref(), &self.population);
+            fs::write(path, text)?;
+        }
+        Ok(fittest)
+    }
+
+    /// Evolve until `config.generations` have been evaluated, returning the fittest candidate.
+    pub fn run(&mut self) -> io::Result<Option<Candidate>> {
+        while self.generation < self.config.generations {
+            self.step()?;
+        }
+        Ok(self.best.clone())
+    }
+}
+
+/// Add Gaussian noise with standard deviation `sigma` to each weight.
+fn mutate<R: Rng>(weights: &[f64], sigma: f64, rng: &mut R) -> Vec<f64> {
+    let noise = Normal::new(0.0, sigma);
+    weights
+        .iter()
+        .map(|weight| weight + noise.sample(rng))
+        .collect()
+}
+
+/// Checkpoints are text, with a `generation` line, then a `best` line with the win rate and
+/// weights of the fittest candidate so far, followed by one line of weights per candidate.
+fn format_checkpoint(
+    generation: usize,
+    best: Option<&Candidate>,
+    population: &[Vec<f64>],
+) -> String {
+    let line = |weights: &[f64]| -> String {
+        let weights: Vec<String> = weights.iter().map(|weight| weight.to_string()).collect();
+        weights.join(" ")
+    };
+    let mut text = format!("generation {}\n", generation);
+    if let Some(best) = best {
+        text.push_str(&format!("best {} {}\n", best.win_rate, line(&best.weights)));
+    }
+    for weights in population {
+        text.push_str(&line(weights));
+        text.push('\n');
+    }
+    text
+}
+
+/// Parse a checkpoint written by `format_checkpoint`. The `best` line is optional.
+fn parse_checkpoint(text: &str) -> io::Result<(usize, Option<Candidate>, Vec<Vec<f64>>)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+    let weights = |line: &str| {
+        line.split_whitespace()
+            .map(|weight| weight.parse())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| invalid("checkpoint has an invalid weight"))
+    };
+    let mut lines = text.lines().peekable();
+    let generation = lines
+        .next()
+        .and_then(|line| line.strip_prefix("generation "))
+        .and_then(|generation| generation.parse().ok())
+        .ok_or_else(|| invalid("checkpoint is missing its generation"))?;
+    let best = match lines.next_if(|line| line.starts_with("best ")) {
+        Some(line) => {
+            let mut values = weights(&line["best ".len()..])?;
+            if values.is_empty() {
+                return Err(invalid("checkpoint is missing the best win rate"));
+            }
+            let win_rate = values.remove(0);
+            Some(Candidate {
+                weights: values,
+                win_rate,
+            })
+        }
+        None => None,
+    };
+    let population = lines.map(weights).collect::<io::Result<_>>()?;
+    Ok((generation, best, population))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_set_map;
+    use crate::tests::{Fixed, Greedy};
+
+    /// Plays greedily with a positive first weight, and resigns otherwise.
+    fn factory(weights: &[f64]) -> Box<dyn Strategy> {
+        match weights[0] > 0.0 {
+            true => Box::new(Greedy(default_set_map())),
+            false => Box::new(Fixed(None)),
+        }
+    }
+
+    #[test]
+    fn test_tuner() {
+        let path = std::env::temp_dir().join(format!("scout-tuner-{}", std::process::id()));
+        let opponents = || -> Vec<Box<dyn Strategy>> {
+            vec![
+                Box::new(Greedy(default_set_map())),
+                Box::new(Greedy(default_set_map())),
+            ]
+        };
+        let config = TunerConfig {
+            population: 6,
+            generations: 3,
+            games: 3,
+            elite: 2,
+            mutation: 1.0,
+            seed: 5,
+            checkpoint: Some(path.clone()),
+            rules: None,
+        };
+
+        // Resigning never wins, so the tuner should find a positive weight
+        let mut tuner = Tuner::new(factory, opponents(), vec![-0.5, 1.0], config.clone());
+        assert_eq!(tuner.evaluate(&[-0.5, 1.0]).unwrap(), 0.0);
+        let best = tuner.run().unwrap().unwrap();
+        assert!(best.weights[0] > 0.0);
+        assert!(best.win_rate > 0.0);
+
+        // Every generation plays the same deals, so the best win rate can be measured again
+        assert_eq!(tuner.evaluate(&best.weights).unwrap(), best.win_rate);
+
+        // The population and best candidate can be resumed from the checkpoint, so a finished
+        // run still has its result
+        let mut resumed = Tuner::resume(factory, opponents(), config.clone()).unwrap();
+        assert_eq!(resumed.generation(), 3);
+        assert_eq!(resumed.population(), tuner.population());
+        assert_eq!(resumed.run().unwrap(), Some(best));
+        fs::remove_file(&path).unwrap();
+
+        // Only the candidate's own failures count against it
+        let resigning = || -> Vec<Box<dyn Strategy>> {
+            vec![Box::new(Fixed(None)), Box::new(Greedy(default_set_map()))]
+        };
+        let config = TunerConfig {
+            checkpoint: None,
+            ..config
+        };
+        let mut tuner = Tuner::new(factory, resigning(), vec![1.0], config);
+        assert!(matches!(
+            tuner.evaluate(&[1.0]),
+            Err(GameError::Resigned(halted)) if halted.seat != 0
+        ));
+        assert!(tuner.step().is_err());
+        assert_eq!(tuner.generation(), 0);
+    }
+}
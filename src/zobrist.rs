@@ -0,0 +1,75 @@
+//! Keys for Zobrist-style hashing of games.
+//!
+//! Each feature of a position (a card at a position in a hand, a player's score, ...) has a key,
+//! and a position hashes to the XOR of the keys of its features. Rather than looking keys up in
+//! tables of random numbers, they are derived by mixing a description of the feature, so there
+//! is no limit on hand sizes or scores.
+
+use crate::{Card, RoundEnd};
+
+const CARD: u64 = 1;
+const CAPTURED: u64 = 2;
+const TOKENS: u64 = 3;
+const SCORE: u64 = 4;
+const HAND_SIZE: u64 = 5;
+const SCOUT_SHOW: u64 = 6;
+const OWNER: u64 = 7;
+const TURN: u64 = 8;
+const ROUND_END: u64 = 9;
+const ROOT: u64 = 10;
+
+/// The SplitMix64 finaliser, which spreads every input bit across the output.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn key(tag: u64, a: u64, b: u64) -> u64 {
+    mix(mix(mix(tag) ^ a) ^ b)
+}
+
+/// Key for `card` at `index` in the active set (`zone` 0), or in the hand of seat `zone - 1`.
+pub(crate) fn card(zone: usize, index: usize, card: &Card) -> u64 {
+    let position = (zone as u64) << 32 | index as u64;
+    let values = (card.0 as u32 as u64) << 32 | card.1 as u32 as u64;
+    key(CARD, position, values)
+}
+
+pub(crate) fn captured(seat: usize, captured: i32) -> u64 {
+    key(CAPTURED, seat as u64, captured as u64)
+}
+
+pub(crate) fn tokens(seat: usize, tokens: i32) -> u64 {
+    key(TOKENS, seat as u64, tokens as u64)
+}
+
+pub(crate) fn score(seat: usize, score: i32) -> u64 {
+    key(SCORE, seat as u64, score as u64)
+}
+
+pub(crate) fn hand_size(seat: usize, size: usize) -> u64 {
+    key(HAND_SIZE, seat as u64, size as u64)
+}
+
+pub(crate) fn scout_show(seat: usize, remaining: u32) -> u64 {
+    key(SCOUT_SHOW, seat as u64, remaining as u64)
+}
+
+pub(crate) fn owner(seat: usize) -> u64 {
+    key(OWNER, seat as u64, 0)
+}
+
+pub(crate) fn turn(seat: usize) -> u64 {
+    key(TURN, seat as u64, 0)
+}
+
+pub(crate) fn round_end(round_end: Option<RoundEnd>) -> u64 {
+    key(ROUND_END, round_end.map_or(0, |end| end as u64 + 1), 0)
+}
+
+/// Key distinguishing positions searched for different root players.
+pub(crate) fn root(seat: usize) -> u64 {
+    key(ROOT, seat as u64, 0)
+}